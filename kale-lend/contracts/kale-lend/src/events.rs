@@ -0,0 +1,211 @@
+use soroban_sdk::{contracttype, symbol_short, Address, Env, Symbol};
+
+use crate::reflector::Asset;
+use crate::PricingMode;
+
+// Event topics. Every event is published as `(topic, subject)` where the subject is the
+// account the event is about (user, borrower or admin); these symbols are stable.
+pub const INITIALIZED: Symbol = symbol_short!("init");
+pub const STAKED: Symbol = symbol_short!("stake");
+pub const UNSTAKED: Symbol = symbol_short!("unstake");
+pub const REWARDS_CLAIMED: Symbol = symbol_short!("claim");
+pub const REWARD_POOL_FUNDED: Symbol = symbol_short!("fund");
+pub const STAKE_ADJUSTED: Symbol = symbol_short!("adjust");
+pub const BORROWED: Symbol = symbol_short!("borrow");
+pub const REPAID: Symbol = symbol_short!("repay");
+pub const LIQUIDATED: Symbol = symbol_short!("liquidate");
+pub const CONFIG_UPDATED: Symbol = symbol_short!("config");
+pub const LIQUIDATION_CONFIG_UPDATED: Symbol = symbol_short!("liq_cfg");
+pub const ORACLE_CONFIG_UPDATED: Symbol = symbol_short!("oracle");
+pub const FALLBACK_ORACLE_SET: Symbol = symbol_short!("fallback");
+pub const RATE_MODEL_UPDATED: Symbol = symbol_short!("rates");
+pub const ORACLE_ASSETS_SET: Symbol = symbol_short!("assets");
+pub const CIRCUIT_BREAKER_CONFIG_UPDATED: Symbol = symbol_short!("breaker");
+pub const TREASURY_SET: Symbol = symbol_short!("treasury");
+pub const FEES_WITHDRAWN: Symbol = symbol_short!("withdraw");
+pub const POSITIONS_MIGRATED: Symbol = symbol_short!("migrate");
+
+// SEP-41 token event topics for kKALE, published in the standard token layout
+pub const TRANSFER: Symbol = symbol_short!("transfer");
+pub const APPROVE: Symbol = symbol_short!("approve");
+pub const MINT: Symbol = symbol_short!("mint");
+pub const BURN: Symbol = symbol_short!("burn");
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InitializedEvent {
+    pub kale_token: Address,
+    pub xlm_token: Address,
+    pub reflector_oracle: Address,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StakedEvent {
+    pub amount: i128,
+    pub kale_amount: i128, // Position size after the stake
+    pub total_staked: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnstakedEvent {
+    pub amount: i128,
+    pub rewards: i128, // Rewards settled before the withdrawal
+    pub kale_amount: i128, // Position size left after the withdrawal
+    pub total_staked: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RewardsClaimedEvent {
+    pub rewards: i128, // Paid to the staker, after the platform fee
+    pub fee: i128,
+    pub total_earned: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RewardPoolFundedEvent {
+    pub amount: i128,
+    pub reward_reserve: i128, // Reserve after the deposit
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StakeAdjustedEvent {
+    pub previous_amount: i128,
+    pub kale_amount: i128,
+    pub kale_price: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BorrowedEvent {
+    pub collateral_amount: i128,
+    pub borrow_amount: i128,
+    pub collateral_ratio: i128, // in basis points
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RepaidEvent {
+    pub amount: i128,
+    pub interest: i128,
+    pub remaining_debt: i128,
+    pub collateral_released: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LiquidatedEvent {
+    pub liquidator: Address,
+    pub repaid: i128,
+    pub collateral_seized: i128,
+    pub collateral_ratio: i128, // in basis points, before liquidation
+    pub bad_debt: i128, // Debt written off because no collateral was left
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConfigUpdatedEvent {
+    pub emission_rate: i128,
+    pub borrowing_apy: i128,
+    pub platform_fee_rate: i128,
+    pub initial_collateral_ratio: i128,
+    pub liquidation_threshold: i128,
+    pub is_active: bool,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LiquidationConfigUpdatedEvent {
+    pub liquidation_bonus: i128,
+    pub close_factor: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RateModelUpdatedEvent {
+    pub base_rate: i128,
+    pub slope1: i128,
+    pub optimal_utilization: i128,
+    pub slope2: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OracleConfigUpdatedEvent {
+    pub max_price_age: u64,
+    pub pricing_mode: PricingMode,
+    pub use_cross_price: bool,
+    pub cross_price_tolerance: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FallbackOracleSetEvent {
+    pub fallback_oracle: Option<Address>,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OracleAssetsSetEvent {
+    pub kale: Asset,
+    pub xlm: Asset,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CircuitBreakerConfigUpdatedEvent {
+    pub max_oracle_deviation: i128,
+    pub max_price_move: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TreasurySetEvent {
+    pub treasury: Address,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FeesWithdrawnEvent {
+    pub to: Address,
+    pub amount: i128,
+    pub treasury_balance: i128, // Fees left after the withdrawal
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PositionsMigratedEvent {
+    pub migrated: u32,
+}
+
+// Publish an event under `(topic, subject)`
+pub(crate) fn publish<D>(env: &Env, topic: Symbol, subject: &Address, data: D)
+where
+    D: soroban_sdk::IntoVal<Env, soroban_sdk::Val>,
+{
+    env.events().publish((topic, subject.clone()), data);
+}
+
+// Publish a kKALE transfer as `(transfer, from, to)`
+pub(crate) fn publish_transfer(env: &Env, from: &Address, to: &Address, amount: i128) {
+    env.events().publish((TRANSFER, from.clone(), to.clone()), amount);
+}
+
+// Publish a kKALE allowance as `(approve, from, spender)`
+pub(crate) fn publish_approve(env: &Env, from: &Address, spender: &Address, amount: i128, expiration_ledger: u32) {
+    env.events().publish((APPROVE, from.clone(), spender.clone()), (amount, expiration_ledger));
+}
+
+// Publish kKALE minted on a stake as `(mint, platform, to)`
+pub(crate) fn publish_mint(env: &Env, to: &Address, amount: i128) {
+    env.events().publish((MINT, env.current_contract_address(), to.clone()), amount);
+}
+
+// Publish kKALE burned as `(burn, from)`
+pub(crate) fn publish_burn(env: &Env, from: &Address, amount: i128) {
+    env.events().publish((BURN, from.clone()), amount);
+}
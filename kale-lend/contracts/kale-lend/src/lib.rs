@@ -1,62 +1,235 @@
 #![no_std]
+#![allow(clippy::too_many_arguments)]
 use soroban_sdk::{
-    contract, contractimpl, contracttype, symbol_short, Address, Env, Map, Symbol, 
-    Error as SorobanError
+    contract, contracterror, contractimpl, contracttype, symbol_short, token, Address, Env, Map,
+    Symbol,
 };
 
 
+mod events;
+mod receipt;
 mod reflector;
-use reflector::{ReflectorClient, Asset as ReflectorAsset};
+pub mod testutils;
+mod valuation;
+use events::{
+    BorrowedEvent, CircuitBreakerConfigUpdatedEvent, ConfigUpdatedEvent, FallbackOracleSetEvent,
+    FeesWithdrawnEvent, InitializedEvent, LiquidatedEvent, LiquidationConfigUpdatedEvent,
+    OracleAssetsSetEvent, OracleConfigUpdatedEvent, PositionsMigratedEvent, RateModelUpdatedEvent,
+    RepaidEvent, RewardPoolFundedEvent, RewardsClaimedEvent, StakeAdjustedEvent, StakedEvent,
+    TreasurySetEvent, UnstakedEvent,
+};
+use reflector::{ReflectorClient, Asset as ReflectorAsset, PriceData};
+use valuation::{Quote, Valuation, USD_DECIMALS};
 
 #[contract]
 pub struct KaleLendingPlatform;
 
+// Errors returned by the platform entry points
+#[contracterror]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[repr(u32)]
+pub enum Error {
+    NotInitialized = 1,
+    AlreadyInitialized = 2,
+    Paused = 3,
+    InvalidAmount = 4,
+    PositionNotFound = 5,
+    InsufficientCollateral = 6,
+    OracleUnavailable = 7,
+    StalePrice = 8,
+    Unauthorized = 9,
+    PositionHealthy = 10,
+    InvalidConfig = 11,
+    InsufficientStake = 12,
+    MathOverflow = 13,
+    PriceDeviation = 14,
+    CircuitBreakerTripped = 15,
+    InsufficientRewardReserve = 16,
+    InsufficientLiquidity = 17,
+    InsufficientAllowance = 18,
+    InsufficientFees = 19,
+}
+
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct StakingPosition {
     pub user: Address,
-    pub kale_amount: i128,
+    pub kale_amount: i128, // KALE value of the shares as of the last update
+    pub shares: i128, // kKALE balance
     pub start_time: u64,
     pub last_claim_time: u64,
     pub auto_adjust_enabled: bool,
     pub price_threshold: i128, // e.g., 500 = 5%
     pub last_adjustment_price: i128,
-    pub total_earned: i128,
+    pub total_earned: i128, // Emission rewards settled so far
+    pub interest_earned: i128, // Lender interest credited to the shares held, net of the fee
+    pub reward_per_token_paid: i128, // reward_per_token_stored when last settled
+    pub interest_per_share_paid: i128, // interest_per_share_stored when last settled
+    pub pending_rewards: i128, // Settled rewards not yet paid out
 }
 
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct BorrowingPosition {
+    pub user: Address,
+    pub borrowed_amount: i128, // Debt including interest, as of the last update
+    pub principal: i128, // Borrowed KALE not yet repaid; repayments cover interest first
+    pub scaled_debt: i128, // Debt divided by the borrow index
+    pub collateral_amount: i128,
+    pub borrow_time: u64,
+    pub interest_rate: i128, // Borrow rate at the last update, in basis points
+    pub last_payment_time: u64,
+    pub total_interest_paid: i128,
+    pub is_active: bool,
+}
+
+// Position layouts stored in the legacy whole-map keys. Frozen: only read by
+// migrate_positions, which converts them to the current layouts.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LegacyStakingPosition {
+    pub user: Address,
+    pub kale_amount: i128,
+    pub start_time: u64,
+    pub last_claim_time: u64,
+    pub auto_adjust_enabled: bool,
+    pub price_threshold: i128,
+    pub last_adjustment_price: i128,
+    pub total_earned: i128,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LegacyBorrowingPosition {
     pub user: Address,
     pub borrowed_amount: i128,
     pub collateral_amount: i128,
     pub borrow_time: u64,
-    pub interest_rate: i128, // in basis points
+    pub interest_rate: i128,
     pub last_payment_time: u64,
     pub total_interest_paid: i128,
     pub is_active: bool,
 }
 
+// Single-struct platform state of instances from before the config split. Frozen: only
+// read by migrate_positions, which splits it into PlatformConfig and PlatformState.
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct PlatformState {
+pub struct LegacyPlatformState {
     pub admin: Address,
     pub kale_token: Address,
-    pub xlm_token: Address, // XLM token for collateral
+    pub xlm_token: Address,
     pub reflector_oracle: Address,
     pub total_staked: i128,
     pub total_borrowed: i128,
     pub total_collateral: i128,
-    pub staking_apy: i128, // Annual yield rate in basis points
-    pub borrowing_apy: i128, // Annual interest rate in basis points
+    pub staking_apy: i128,
+    pub borrowing_apy: i128,
+    pub current_kale_price: i128,
+    pub current_xlm_price: i128,
+    pub last_price_update: u64,
+    pub platform_fee_rate: i128,
+    pub liquidation_threshold: i128,
+    pub is_active: bool,
+}
+
+// Pool accounting, rewritten as loans, stakes and rewards move
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlatformState {
+    pub kale_token: Address,
+    pub xlm_token: Address, // XLM token for collateral
+    pub total_staked: i128, // Staked KALE lent to borrowers, including compounded interest
+    pub total_shares: i128, // kKALE supply, each share redeemable for total_staked / total_shares
+    pub total_borrowed: i128, // Debt including interest, as of the last accrual
+    pub total_scaled_debt: i128, // Sum of every position's scaled debt
+    pub borrow_index: i128, // Cumulative borrow interest factor, scaled by INDEX_SCALE
+    pub last_accrual_time: u64,
+    pub total_collateral: i128,
+    pub emission_rate: i128, // KALE emitted to stakers per second, shared by stake
+    pub reward_per_token_stored: i128, // Rewards per kKALE share so far, scaled by REWARD_SCALE
+    pub interest_per_share_stored: i128, // Lender interest per kKALE share so far, scaled by REWARD_SCALE
+    pub last_reward_update: u64,
+    pub total_rewards_emitted: i128, // Emission accrued to stakers so far, paid or not
+    pub reward_reserve: i128, // KALE funded for staking rewards, held apart from stakes
+    pub accrued_fees: i128, // Platform's cut of accrued interest not yet paid by borrowers
+    pub treasury: Address, // Receives withdrawn platform fees
+    pub treasury_balance: i128, // Platform fees collected and held for the treasury, not lendable
     pub current_kale_price: i128,
     pub current_xlm_price: i128, // Current XLM price in USD
     pub last_price_update: u64,
     pub platform_fee_rate: i128, // Platform fee in basis points
-    pub liquidation_threshold: i128, // Collateral ratio threshold
     pub is_active: bool,
 }
 
+// Admin-set oracle, pricing, liquidation and rate parameters, only written by the admin
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PlatformConfig {
+    pub admin: Address,
+    pub reflector_oracle: Address,
+    pub fallback_oracle: Option<Address>, // Tried when the primary oracle has no usable quote
+    pub oracle_assets: OracleAssets,
+    pub max_price_age: u64, // Oldest oracle quote accepted, in seconds
+    pub pricing_mode: PricingMode,
+    pub use_cross_price: bool, // Value positions from the KALE/XLM cross quote
+    pub cross_price_tolerance: i128, // Max cross vs spot-ratio disagreement in basis points
+    pub max_oracle_deviation: i128, // Max primary vs fallback disagreement in basis points, 0 = off
+    pub max_price_move: i128, // Max move between consecutive ticks in basis points, 0 = off
+    pub initial_collateral_ratio: i128, // Collateral ratio required to open or grow a loan
+    pub liquidation_threshold: i128, // Collateral ratio threshold
+    pub liquidation_bonus: i128, // Extra collateral paid to liquidators in basis points
+    pub close_factor: i128, // Max share of debt repaid per liquidation in basis points
+    pub borrowing_apy: i128, // Annual interest rate at zero utilization in basis points
+    pub rate_slope1: i128, // Rate added from zero up to optimal utilization
+    pub optimal_utilization: i128, // Kink point in basis points
+    pub rate_slope2: i128, // Rate added from optimal up to full utilization
+}
+
+// Identifiers the oracles quote each token under
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OracleAssets {
+    pub kale: ReflectorAsset,
+    pub xlm: ReflectorAsset,
+}
+
+// How oracle quotes become the prices used for borrowing, liquidation and auto-adjust
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PricingMode {
+    Spot, // Latest quote
+    Twap(u32), // Average of the last N quotes
+    Conservative(u32), // Worse of spot and N-quote TWAP: lower for XLM collateral, higher for KALE
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OracleStatus {
+    pub oracle: Address,
+    pub fallback_oracle: Option<Address>,
+    pub last_timestamp: u64, // Most recent oracle update
+    pub resolution: u32, // Oracle tick period in seconds
+    pub max_price_age: u64,
+    pub is_stale: bool,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InterestRates {
+    pub utilization: i128, // total_borrowed over lendable KALE, in basis points
+    pub borrow_rate: i128, // Annual rate in basis points
+    pub supply_rate: i128, // Annual rate earned by stakers in basis points
+}
+
+// A staker's earnings to date, split by source
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StakerEarnings {
+    pub emission_rewards: i128, // From the reward emission, paid from the reward reserve
+    pub fee_share: i128, // Share of borrow interest after the platform fee
+}
+
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct YieldPool {
@@ -71,8 +244,9 @@ pub struct YieldPool {
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum StorageKey {
     PlatformState,
-    StakingPositions,
-    BorrowingPositions,
+    PlatformConfig,
+    StakingPositions, // Legacy whole-map key, only read by migrate_positions
+    BorrowingPositions, // Legacy whole-map key, only read by migrate_positions
     YieldPool,
 }
 
@@ -80,6 +254,7 @@ impl StorageKey {
     fn to_symbol(&self) -> Symbol {
         match self {
             StorageKey::PlatformState => symbol_short!("STATE"),
+            StorageKey::PlatformConfig => symbol_short!("CONFIG"),
             StorageKey::StakingPositions => symbol_short!("STAKES"),
             StorageKey::BorrowingPositions => symbol_short!("BORROWS"),
             StorageKey::YieldPool => symbol_short!("YIELD"),
@@ -89,7 +264,7 @@ impl StorageKey {
     // Helper method to get data from storage
     fn get<T>(&self, env: &Env) -> Option<T> 
     where 
-        T: soroban_sdk::IntoVal<Env, soroban_sdk::Val> + soroban_sdk::FromVal<Env, soroban_sdk::Val>,
+        T: soroban_sdk::IntoVal<Env, soroban_sdk::Val> + soroban_sdk::TryFromVal<Env, soroban_sdk::Val>,
     {
         env.storage().instance().get(&self.to_symbol())
     }
@@ -97,18 +272,114 @@ impl StorageKey {
     // Helper method to set data in storage
     fn set<T>(&self, env: &Env, value: &T) 
     where 
-        T: soroban_sdk::IntoVal<Env, soroban_sdk::Val> + soroban_sdk::FromVal<Env, soroban_sdk::Val>,
+        T: soroban_sdk::IntoVal<Env, soroban_sdk::Val> + soroban_sdk::TryFromVal<Env, soroban_sdk::Val>,
     {
         env.storage().instance().set(&self.to_symbol(), value);
+        env.storage().instance().extend_ttl(INSTANCE_LIFETIME_THRESHOLD, INSTANCE_BUMP_AMOUNT);
     }
 
     // Helper method to check if key exists in storage
     fn has(&self, env: &Env) -> bool {
         env.storage().instance().has(&self.to_symbol())
     }
+
+    // Helper method to delete data from storage
+    fn remove(&self, env: &Env) {
+        env.storage().instance().remove(&self.to_symbol());
+    }
+}
+
+// Per-user position keys, each stored as its own persistent entry
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DataKey {
+    Stake(Address),
+    Borrow(Address),
+    Allowance(Address, Address), // kKALE allowance, by owner and spender
+}
+
+impl DataKey {
+    // Helper method to get data from storage, extending its TTL on read
+    fn get<T>(&self, env: &Env) -> Option<T>
+    where
+        T: soroban_sdk::IntoVal<Env, soroban_sdk::Val> + soroban_sdk::TryFromVal<Env, soroban_sdk::Val>,
+    {
+        let value = env.storage().persistent().get(self);
+        if value.is_some() {
+            self.extend_ttl(env);
+        }
+        value
+    }
+
+    // Helper method to set data in storage and extend its TTL
+    fn set<T>(&self, env: &Env, value: &T)
+    where
+        T: soroban_sdk::IntoVal<Env, soroban_sdk::Val> + soroban_sdk::TryFromVal<Env, soroban_sdk::Val>,
+    {
+        env.storage().persistent().set(self, value);
+        self.extend_ttl(env);
+    }
+
+    // Helper method to check if key exists in storage
+    fn has(&self, env: &Env) -> bool {
+        env.storage().persistent().has(self)
+    }
+
+    // Helper method to delete data from storage
+    fn remove(&self, env: &Env) {
+        env.storage().persistent().remove(self);
+    }
+
+    fn extend_ttl(&self, env: &Env) {
+        env.storage().persistent().extend_ttl(self, POSITION_LIFETIME_THRESHOLD, POSITION_BUMP_AMOUNT);
+    }
 }
 
+// Storage TTLs, in ledgers (~5s each)
+const DAY_IN_LEDGERS: u32 = 17280;
+const INSTANCE_BUMP_AMOUNT: u32 = 7 * DAY_IN_LEDGERS;
+const INSTANCE_LIFETIME_THRESHOLD: u32 = INSTANCE_BUMP_AMOUNT - DAY_IN_LEDGERS;
+const POSITION_BUMP_AMOUNT: u32 = 30 * DAY_IN_LEDGERS;
+const POSITION_LIFETIME_THRESHOLD: u32 = POSITION_BUMP_AMOUNT - DAY_IN_LEDGERS;
+
+// Default oldest oracle quote accepted: three 5 minute Reflector ticks
+const DEFAULT_MAX_PRICE_AGE: u64 = 15 * 60;
+
+// Default accepted gap between the KALE/XLM cross quote and the ratio of the USD quotes
+const DEFAULT_CROSS_PRICE_TOLERANCE: i128 = 100; // 1%
+
+// Default accepted gap between the primary and fallback oracles (basis points)
+const DEFAULT_MAX_ORACLE_DEVIATION: i128 = 500; // 5%
+
+// Default auto-adjust price threshold for new staking positions (basis points)
+const DEFAULT_PRICE_THRESHOLD: i128 = 500; // 5%
+
+// Fixed-point scale of the borrow index
+const INDEX_SCALE: i128 = 1_000_000_000_000_000_000;
+
+const SECONDS_PER_YEAR: i128 = 365 * 24 * 60 * 60;
+const SECONDS_PER_DAY: i128 = 24 * 60 * 60;
+
+// Fixed-point scale of the staking reward accumulator
+const REWARD_SCALE: i128 = 1_000_000_000_000_000_000;
+
+// Virtual kKALE share and KALE unit added to both sides of the exchange rate, so a
+// nearly empty pool cannot have its rate inflated against the next staker
+const VIRTUAL_STAKE: i128 = 1;
+
+// Largest share of a stake's value that may be lost to share rounding (basis points)
+const MAX_STAKE_ROUNDING_LOSS: i128 = 1;
+
+// Default kink of the interest rate model. Both slopes start at zero, so the borrow rate
+// stays at `borrowing_apy` until the admin configures the model.
+const DEFAULT_OPTIMAL_UTILIZATION: i128 = 8000; // 80%
+
+// Default liquidation parameters (basis points)
+const DEFAULT_LIQUIDATION_BONUS: i128 = 500; // 5%
+const DEFAULT_CLOSE_FACTOR: i128 = 5000; // 50%
+
 // Testnet Reflector Oracle Addresses
+#[allow(dead_code)]
 const STELLAR_ORACLE: &str = "CAVLP5DH2GJPZMVO7IJY4CVOD5MWEFTJFVPD2YY2FQXOQHRGHK4D6HLP"; // Stellar Pubnet
 
 #[contractimpl]
@@ -120,33 +391,71 @@ impl KaleLendingPlatform {
         kale_token: Address,
         xlm_token: Address, // XLM token for collateral
         reflector_oracle: Address,
-        staking_apy: i128,
+        emission_rate: i128,
         borrowing_apy: i128,
         platform_fee_rate: i128,
+        initial_collateral_ratio: i128,
         liquidation_threshold: i128,
-    ) -> Result<(), SorobanError> {
+        oracle_assets: Option<OracleAssets>, // Defaults to the KALE and XLM symbols
+    ) -> Result<(), Error> {
         if StorageKey::PlatformState.has(&env) {
-            return Err(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ));
+            return Err(Error::AlreadyInitialized);
+        }
+
+        admin.require_auth();
+
+        Self::validate_collateral_ratios(initial_collateral_ratio, liquidation_threshold)?;
+        if emission_rate < 0 || borrowing_apy < 0 || !(0..=10000).contains(&platform_fee_rate) {
+            return Err(Error::InvalidConfig);
         }
 
+        let oracle_assets = oracle_assets.unwrap_or_else(Self::default_oracle_assets);
+        Self::validate_oracle_assets(&oracle_assets)?;
+
+        let config = PlatformConfig {
+            admin: admin.clone(),
+            reflector_oracle,
+            fallback_oracle: None,
+            oracle_assets,
+            max_price_age: DEFAULT_MAX_PRICE_AGE,
+            pricing_mode: PricingMode::Spot,
+            use_cross_price: false,
+            cross_price_tolerance: DEFAULT_CROSS_PRICE_TOLERANCE,
+            max_oracle_deviation: DEFAULT_MAX_ORACLE_DEVIATION,
+            max_price_move: 0,
+            initial_collateral_ratio,
+            liquidation_threshold,
+            liquidation_bonus: DEFAULT_LIQUIDATION_BONUS,
+            close_factor: DEFAULT_CLOSE_FACTOR,
+            borrowing_apy,
+            rate_slope1: 0,
+            optimal_utilization: DEFAULT_OPTIMAL_UTILIZATION,
+            rate_slope2: 0,
+        };
+
         let state = PlatformState {
-            admin,
             kale_token,
             xlm_token,
-            reflector_oracle,
             total_staked: 0,
+            total_shares: 0,
             total_borrowed: 0,
+            total_scaled_debt: 0,
+            borrow_index: INDEX_SCALE,
+            last_accrual_time: env.ledger().timestamp(),
             total_collateral: 0,
-            staking_apy,
-            borrowing_apy,
+            emission_rate,
+            reward_per_token_stored: 0,
+            interest_per_share_stored: 0,
+            last_reward_update: env.ledger().timestamp(),
+            total_rewards_emitted: 0,
+            reward_reserve: 0,
+            accrued_fees: 0,
+            treasury: admin.clone(),
+            treasury_balance: 0,
             current_kale_price: 0,
             current_xlm_price: 0,
             last_price_update: env.ledger().timestamp(),
             platform_fee_rate,
-            liquidation_threshold,
             is_active: true,
         };
 
@@ -158,143 +467,192 @@ impl KaleLendingPlatform {
             last_distribution_time: env.ledger().timestamp(),
         };
 
+        StorageKey::PlatformConfig.set(&env, &config);
         StorageKey::PlatformState.set(&env, &state);
         StorageKey::YieldPool.set(&env, &yield_pool);
-        
-        // Initialize empty maps
-        let staking_positions: Map<Address, StakingPosition> = Map::new(&env);
-        let borrowing_positions: Map<Address, BorrowingPosition> = Map::new(&env);
-        StorageKey::StakingPositions.set(&env, &staking_positions);
-        StorageKey::BorrowingPositions.set(&env, &borrowing_positions);
+
+        events::publish(
+            &env,
+            events::INITIALIZED,
+            &config.admin,
+            InitializedEvent {
+                kale_token: state.kale_token.clone(),
+                xlm_token: state.xlm_token.clone(),
+                reflector_oracle: config.reflector_oracle.clone(),
+            },
+        );
         
         Ok(())
     }
 
-    // Stake KALE tokens to earn yield
+    // Stake KALE tokens to earn yield. Staking again tops up the existing position after
+    // settling its pending rewards; auto-adjust settings only change when passed explicitly.
     pub fn stake_kale(
         env: Env,
         user: Address,
         amount: i128,
-        auto_adjust_enabled: bool,
-        price_threshold_percent: u32,
-    ) -> Result<(), SorobanError> {
+        auto_adjust_enabled: Option<bool>,
+        price_threshold_percent: Option<u32>,
+    ) -> Result<(), Error> {
+        user.require_auth();
+
         if amount <= 0 {
-            return Err(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ));
+            return Err(Error::InvalidAmount);
         }
 
-        let mut state: PlatformState = env.storage().instance().get(&StorageKey::PlatformState.to_symbol())
-            .ok_or(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ))?;
+        let mut state: PlatformState = StorageKey::PlatformState.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        let config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
 
         if !state.is_active {
-            return Err(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ));
+            return Err(Error::Paused);
         }
 
-        let mut staking_positions: Map<Address, StakingPosition> = env.storage().instance().get(&StorageKey::StakingPositions.to_symbol())
-            .unwrap_or(Map::new(&env));
+        Self::accrue_borrow_index(&env, &config, &mut state);
+        Self::update_reward_per_token(&env, &mut state);
 
         // Update current KALE price
-        let current_price = Self::get_kale_price(&env, &state.reflector_oracle)?;
+        let current_price = Self::get_kale_price(&env, &config)?;
         state.current_kale_price = current_price;
         state.last_price_update = env.ledger().timestamp();
 
-        // Create or update staking position
-        let position = StakingPosition {
-            user: user.clone(),
-            kale_amount: amount,
-            start_time: env.ledger().timestamp(),
-            last_claim_time: env.ledger().timestamp(),
-            auto_adjust_enabled,
-            price_threshold: (price_threshold_percent as i128) * 100, // Convert to basis points
-            last_adjustment_price: current_price,
-            total_earned: 0,
+        // Create or top up staking position
+        let mut position = match DataKey::Stake(user.clone()).get::<StakingPosition>(&env) {
+            Some(mut position) => {
+                Self::settle_staking_rewards(&env, &state, &mut position)?;
+                position
+            }
+            None => Self::new_staking_position(&env, &state, &user, current_price),
         };
 
-        staking_positions.set(user, position);
-        state.total_staked += amount;
+        Self::deposit_stake(&env, &mut state, &user, &mut position, amount)?;
+
+        if let Some(enabled) = auto_adjust_enabled {
+            position.auto_adjust_enabled = enabled;
+        }
+        if let Some(percent) = price_threshold_percent {
+            position.price_threshold = (percent as i128) * 100; // Convert to basis points
+        }
 
-        env.storage().instance().set(&StorageKey::StakingPositions.to_symbol(), &staking_positions);
-        env.storage().instance().set(&StorageKey::PlatformState.to_symbol(), &state);
+        DataKey::Stake(user.clone()).set(&env, &position);
+        StorageKey::PlatformState.set(&env, &state);
+
+        events::publish(
+            &env,
+            events::STAKED,
+            &user,
+            StakedEvent {
+                amount,
+                kale_amount: position.kale_amount,
+                total_staked: state.total_staked,
+            },
+        );
 
         Ok(())
     }
 
-    // Borrow KALE using XLM as collateral (proper DeFi lending)
+    // Borrow KALE using XLM as collateral (proper DeFi lending). Borrowing again adds to the
+    // active position, and the combined debt including interest is re-checked.
     pub fn borrow_kale_with_xlm(
         env: Env,
         user: Address,
         xlm_collateral_amount: i128,
         kale_borrow_amount: i128,
-    ) -> Result<(), SorobanError> {
+    ) -> Result<(), Error> {
+        user.require_auth();
+
         if xlm_collateral_amount <= 0 || kale_borrow_amount <= 0 {
-            return Err(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ));
+            return Err(Error::InvalidAmount);
         }
 
-        let mut state: PlatformState = env.storage().instance().get(&StorageKey::PlatformState.to_symbol())
-            .ok_or(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ))?;
+        let mut state: PlatformState = StorageKey::PlatformState.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        let config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
 
         if !state.is_active {
-            return Err(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ));
+            return Err(Error::Paused);
         }
 
+        Self::check_circuit_breaker(&env, &config)?;
+
         // Get current prices from Reflector oracle
-        let kale_price_usd = Self::get_kale_price(&env, &state.reflector_oracle)?;
-        let xlm_price_usd = Self::get_xlm_price(&env, &state.reflector_oracle)?;
-        
-        // Calculate collateral value in USD (assuming 6 decimals for both KALE and XLM)
-        let collateral_value_usd = (xlm_collateral_amount * xlm_price_usd) / 1000000;
-        let borrow_value_usd = (kale_borrow_amount * kale_price_usd) / 1000000;
-        
-        // Check collateral ratio (e.g., 150% = 15000 basis points)
-        let collateral_ratio = (collateral_value_usd * 10000) / borrow_value_usd;
+        let kale_price_usd = Self::get_kale_price(&env, &config)?;
+        let xlm_price_usd = Self::get_xlm_price(&env, &config)?;
 
-        if collateral_ratio < state.liquidation_threshold {
-            return Err(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ));
-        }
+        Self::accrue_borrow_index(&env, &config, &mut state);
 
-        let mut borrowing_positions: Map<Address, BorrowingPosition> = env.storage().instance().get(&StorageKey::BorrowingPositions.to_symbol())
-            .unwrap_or(Map::new(&env));
+        // Loans are funded from staked KALE that is not already lent out
+        if kale_borrow_amount > Self::available_liquidity(&env, &state) {
+            return Err(Error::InsufficientLiquidity);
+        }
 
-        let position = BorrowingPosition {
-            user: user.clone(),
-            borrowed_amount: kale_borrow_amount,
-            collateral_amount: xlm_collateral_amount,
-            borrow_time: env.ledger().timestamp(),
-            interest_rate: state.borrowing_apy,
-            last_payment_time: env.ledger().timestamp(),
-            total_interest_paid: 0,
-            is_active: true,
+        // Create or add to the borrowing position
+        let active_position = DataKey::Borrow(user.clone()).get::<BorrowingPosition>(&env)
+            .filter(|position| position.is_active);
+        let mut position = match active_position {
+            Some(position) => position,
+            None => BorrowingPosition {
+                user: user.clone(),
+                borrowed_amount: 0,
+                principal: 0,
+                scaled_debt: 0,
+                collateral_amount: 0,
+                borrow_time: env.ledger().timestamp(),
+                interest_rate: 0, // Set once the loan is counted in utilization
+                last_payment_time: env.ledger().timestamp(),
+                total_interest_paid: 0,
+                is_active: true,
+            },
         };
+        let scaled_amount = Self::debt_to_scaled(kale_borrow_amount, state.borrow_index);
+        position.collateral_amount += xlm_collateral_amount;
+        position.principal += kale_borrow_amount;
+        position.scaled_debt += scaled_amount;
+        position.borrowed_amount = Self::scaled_to_debt(position.scaled_debt, state.borrow_index);
+
+        // Check collateral ratio against the opening requirement (e.g., 150% = 15000 basis points)
+        let valuation = Valuation::load(&env, &state);
+        let quote = Self::get_quote(&env, &config, &valuation, kale_price_usd, xlm_price_usd)?;
+        let collateral_ratio = valuation.collateral_ratio(
+            position.collateral_amount,
+            position.borrowed_amount,
+            &quote,
+        )?;
+
+        if collateral_ratio < config.initial_collateral_ratio {
+            return Err(Error::InsufficientCollateral);
+        }
+
+        // Lock the XLM collateral and pay out the borrowed KALE
+        let contract_address = env.current_contract_address();
+        let xlm_client = token::Client::new(&env, &state.xlm_token);
+        let kale_client = token::Client::new(&env, &state.kale_token);
+        xlm_client.transfer(&user, &contract_address, &xlm_collateral_amount);
+        kale_client.transfer(&contract_address, &user, &kale_borrow_amount);
 
-        borrowing_positions.set(user, position);
-        state.total_borrowed += kale_borrow_amount;
+        state.total_scaled_debt += scaled_amount;
+        state.total_borrowed = Self::scaled_to_debt(state.total_scaled_debt, state.borrow_index);
         state.total_collateral += xlm_collateral_amount;
+        position.interest_rate = Self::interest_rates(&env, &config, &state).borrow_rate;
+        DataKey::Borrow(user.clone()).set(&env, &position);
         state.current_kale_price = kale_price_usd;
         state.current_xlm_price = xlm_price_usd;
         state.last_price_update = env.ledger().timestamp();
 
-        env.storage().instance().set(&StorageKey::BorrowingPositions.to_symbol(), &borrowing_positions);
-        env.storage().instance().set(&StorageKey::PlatformState.to_symbol(), &state);
+        StorageKey::PlatformState.set(&env, &state);
+
+        events::publish(
+            &env,
+            events::BORROWED,
+            &user,
+            BorrowedEvent {
+                collateral_amount: xlm_collateral_amount,
+                borrow_amount: kale_borrow_amount,
+                collateral_ratio,
+            },
+        );
 
         Ok(())
     }
@@ -304,134 +662,347 @@ impl KaleLendingPlatform {
         env: Env,
         user: Address,
         repay_amount: i128,
-    ) -> Result<i128, SorobanError> {
+    ) -> Result<i128, Error> {
+        user.require_auth();
+
         if repay_amount <= 0 {
-            return Err(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ));
+            return Err(Error::InvalidAmount);
         }
 
-        let mut state: PlatformState = env.storage().instance().get(&StorageKey::PlatformState.to_symbol())
-            .ok_or(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ))?;
-
-        let mut borrowing_positions: Map<Address, BorrowingPosition> = env.storage().instance().get(&StorageKey::BorrowingPositions.to_symbol())
-            .unwrap_or(Map::new(&env));
+        let mut state: PlatformState = StorageKey::PlatformState.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        let config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
 
-        let mut position = borrowing_positions.get(user.clone())
-            .ok_or(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ))?;
+        let mut position: BorrowingPosition = DataKey::Borrow(user.clone()).get(&env)
+            .ok_or(Error::PositionNotFound)?;
 
         if !position.is_active {
-            return Err(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ));
+            return Err(Error::PositionNotFound);
         }
 
-        // Calculate interest owed
-        let time_elapsed = (env.ledger().timestamp() - position.last_payment_time) as i128;
-        let interest_owed = (position.borrowed_amount * position.interest_rate * time_elapsed) / (365 * 24 * 60 * 60 * 10000);
-        
-        let total_repay_needed = position.borrowed_amount + interest_owed;
-        let actual_repay = if repay_amount >= total_repay_needed {
-            total_repay_needed
-        } else {
-            repay_amount
-        };
+        // Bring the debt up to date and cap the repayment at it
+        Self::accrue_borrow_index(&env, &config, &mut state);
+        position.borrowed_amount = Self::scaled_to_debt(position.scaled_debt, state.borrow_index);
+        let actual_repay = repay_amount.min(position.borrowed_amount);
+
+        // Pull the repayment into the contract
+        let contract_address = env.current_contract_address();
+        let kale_client = token::Client::new(&env, &state.kale_token);
+        kale_client.transfer(&user, &contract_address, &actual_repay);
 
         // Update position
-        position.borrowed_amount -= actual_repay;
-        position.total_interest_paid += interest_owed;
+        let interest_paid = Self::apply_repayment(&mut state, &mut position, actual_repay);
+        Self::collect_interest_fee(&env, &mut state, interest_paid)?;
         position.last_payment_time = env.ledger().timestamp();
 
         // If fully repaid, release XLM collateral
-        if position.borrowed_amount <= 0 {
+        let mut collateral_released = 0;
+        if position.scaled_debt == 0 {
+            let xlm_client = token::Client::new(&env, &state.xlm_token);
+            xlm_client.transfer(&contract_address, &user, &position.collateral_amount);
+
             position.is_active = false;
             state.total_collateral -= position.collateral_amount;
+            collateral_released = position.collateral_amount;
         }
 
-        state.total_borrowed -= actual_repay;
+        position.interest_rate = Self::interest_rates(&env, &config, &state).borrow_rate;
+
+        DataKey::Borrow(user.clone()).set(&env, &position);
+        StorageKey::PlatformState.set(&env, &state);
 
-        borrowing_positions.set(user, position);
-        env.storage().instance().set(&StorageKey::BorrowingPositions.to_symbol(), &borrowing_positions);
-        env.storage().instance().set(&StorageKey::PlatformState.to_symbol(), &state);
+        events::publish(
+            &env,
+            events::REPAID,
+            &user,
+            RepaidEvent {
+                amount: actual_repay,
+                interest: interest_paid,
+                remaining_debt: position.borrowed_amount,
+                collateral_released,
+            },
+        );
 
         Ok(actual_repay)
     }
 
-    // Claim staking rewards
-    pub fn claim_staking_rewards(env: Env, user: Address) -> Result<i128, SorobanError> {
-        let state: PlatformState = env.storage().instance().get(&StorageKey::PlatformState.to_symbol())
-            .ok_or(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ))?;
-
-        let staking_positions: Map<Address, StakingPosition> = env.storage().instance().get(&StorageKey::StakingPositions.to_symbol())
-            .unwrap_or(Map::new(&env));
-
-        let mut position = staking_positions.get(user.clone())
-            .ok_or(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ))?;
-
-        // Calculate rewards based on time staked and amount
-        let time_staked = (env.ledger().timestamp() - position.last_claim_time) as i128;
-        let rewards = (position.kale_amount * state.staking_apy * time_staked) / (365 * 24 * 60 * 60 * 10000);
+    // Liquidate an undercollateralized borrowing position. The liquidator repays part of
+    // the borrower's KALE debt (capped by the close factor) and receives the equivalent
+    // XLM collateral plus the liquidation bonus. Returns the XLM seized.
+    pub fn liquidate(
+        env: Env,
+        liquidator: Address,
+        borrower: Address,
+        repay_amount: i128,
+    ) -> Result<i128, Error> {
+        liquidator.require_auth();
+
+        if repay_amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let mut state: PlatformState = StorageKey::PlatformState.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        let config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        if !state.is_active {
+            return Err(Error::Paused);
+        }
+
+        let mut position: BorrowingPosition = DataKey::Borrow(borrower.clone()).get(&env)
+            .ok_or(Error::PositionNotFound)?;
+
+        if !position.is_active {
+            return Err(Error::PositionNotFound);
+        }
+
+        // Re-price the position with current oracle prices
+        Self::check_circuit_breaker(&env, &config)?;
+        let kale_price_usd = Self::get_kale_price(&env, &config)?;
+        let xlm_price_usd = Self::get_xlm_price(&env, &config)?;
+
+        Self::accrue_borrow_index(&env, &config, &mut state);
+        position.borrowed_amount = Self::scaled_to_debt(position.scaled_debt, state.borrow_index);
+        let total_debt = position.borrowed_amount;
+
+        let valuation = Valuation::load(&env, &state);
+        let quote = Self::get_quote(&env, &config, &valuation, kale_price_usd, xlm_price_usd)?;
+        let collateral_ratio = valuation.collateral_ratio(
+            position.collateral_amount,
+            total_debt,
+            &quote,
+        )?;
+
+        if collateral_ratio >= config.liquidation_threshold {
+            return Err(Error::PositionHealthy);
+        }
+
+        // Cap the repayment at the close factor
+        let max_repay = (total_debt * config.close_factor) / 10000;
+        let actual_repay = if repay_amount > max_repay { max_repay } else { repay_amount };
+        if actual_repay <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        // Collateral owed to the liquidator: repaid KALE plus bonus, converted to XLM
+        let repay_with_bonus = actual_repay
+            .checked_mul(10000 + config.liquidation_bonus)
+            .ok_or(Error::MathOverflow)?
+            / 10000;
+        let seize_amount = valuation.kale_in_xlm(repay_with_bonus, &quote)?;
+        let seize_amount = if seize_amount > position.collateral_amount {
+            position.collateral_amount
+        } else {
+            seize_amount
+        };
+        // The liquidator must get some collateral for the KALE they repay
+        if seize_amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let contract_address = env.current_contract_address();
+        let kale_client = token::Client::new(&env, &state.kale_token);
+        let xlm_client = token::Client::new(&env, &state.xlm_token);
+        kale_client.transfer(&liquidator, &contract_address, &actual_repay);
+        xlm_client.transfer(&contract_address, &liquidator, &seize_amount);
 
         // Update position
-        position.last_claim_time = env.ledger().timestamp();
-        position.total_earned += rewards;
+        let interest_paid = Self::apply_repayment(&mut state, &mut position, actual_repay);
+        Self::collect_interest_fee(&env, &mut state, interest_paid)?;
+        position.collateral_amount -= seize_amount;
+        state.total_collateral -= seize_amount;
 
-        // Update storage
-        let mut updated_positions = staking_positions;
-        updated_positions.set(user, position);
-        env.storage().instance().set(&StorageKey::StakingPositions.to_symbol(), &updated_positions);
-
-        // Update yield pool
-        let mut yield_pool: YieldPool = env.storage().instance().get(&StorageKey::YieldPool.to_symbol())
-            .ok_or(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ))?;
-        
-        yield_pool.staking_rewards += rewards;
-        yield_pool.total_rewards_distributed += rewards;
+        // Release whatever collateral is left once the debt is cleared
+        let mut bad_debt = 0;
+        if position.scaled_debt == 0 {
+            xlm_client.transfer(&contract_address, &borrower, &position.collateral_amount);
+
+            position.is_active = false;
+            state.total_collateral -= position.collateral_amount;
+        } else if position.collateral_amount == 0 {
+            bad_debt = Self::write_off_debt(&mut state, &mut position);
+        }
+
+        position.interest_rate = Self::interest_rates(&env, &config, &state).borrow_rate;
+        state.current_kale_price = kale_price_usd;
+        state.current_xlm_price = xlm_price_usd;
+        state.last_price_update = env.ledger().timestamp();
+
+        DataKey::Borrow(borrower.clone()).set(&env, &position);
+        StorageKey::PlatformState.set(&env, &state);
+
+        events::publish(
+            &env,
+            events::LIQUIDATED,
+            &borrower,
+            LiquidatedEvent {
+                liquidator,
+                repaid: actual_repay,
+                collateral_seized: seize_amount,
+                collateral_ratio,
+                bad_debt,
+            },
+        );
+
+        Ok(seize_amount)
+    }
+
+    // Withdraw staked KALE, burning the kKALE it is worth. Pending rewards are settled
+    // first; the position is removed once nothing is left staked.
+    pub fn unstake_kale(env: Env, user: Address, amount: i128) -> Result<i128, Error> {
+        user.require_auth();
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let mut state: PlatformState = StorageKey::PlatformState.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        let config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        let mut position: StakingPosition = DataKey::Stake(user.clone()).get(&env)
+            .ok_or(Error::PositionNotFound)?;
+
+        Self::accrue_borrow_index(&env, &config, &mut state);
+        Self::update_reward_per_token(&env, &mut state);
+        let rewards = Self::settle_staking_rewards(&env, &state, &mut position)?;
+
+        Self::withdraw_stake(&env, &mut state, &user, &mut position, amount)?;
+
+        // A fully withdrawn position is kept until its rewards have been claimed
+        if position.shares == 0 && position.pending_rewards == 0 {
+            DataKey::Stake(user.clone()).remove(&env);
+        } else {
+            DataKey::Stake(user.clone()).set(&env, &position);
+        }
+        StorageKey::PlatformState.set(&env, &state);
+
+        events::publish(
+            &env,
+            events::UNSTAKED,
+            &user,
+            UnstakedEvent {
+                amount,
+                rewards,
+                kale_amount: position.kale_amount,
+                total_staked: state.total_staked,
+            },
+        );
+
+        Ok(amount)
+    }
+
+    // Claim staking rewards
+    pub fn claim_staking_rewards(env: Env, user: Address) -> Result<i128, Error> {
+        user.require_auth();
+
+        let mut state: PlatformState = StorageKey::PlatformState.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        let config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        let mut position: StakingPosition = DataKey::Stake(user.clone()).get(&env)
+            .ok_or(Error::PositionNotFound)?;
+
+        Self::accrue_borrow_index(&env, &config, &mut state);
+        Self::update_reward_per_token(&env, &mut state);
+        Self::settle_staking_rewards(&env, &state, &mut position)?;
+
+        // Pay everything owed out of the reward reserve, or nothing if it falls short. The
+        // platform fee is kept back for the treasury.
+        let owed = position.pending_rewards;
+        if owed > state.reward_reserve {
+            return Err(Error::InsufficientRewardReserve);
+        }
+        state.reward_reserve -= owed;
+        position.pending_rewards = 0;
+        let fee = (owed * state.platform_fee_rate) / 10000;
+        let rewards = owed - fee;
+
+        let mut yield_pool: YieldPool = StorageKey::YieldPool.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        yield_pool.total_rewards_distributed += owed;
         yield_pool.last_distribution_time = env.ledger().timestamp();
-        env.storage().instance().set(&StorageKey::YieldPool.to_symbol(), &yield_pool);
+        StorageKey::YieldPool.set(&env, &yield_pool);
+        Self::collect_fees(&env, &mut state, fee)?;
+
+        // Update storage
+        if position.shares == 0 {
+            DataKey::Stake(user.clone()).remove(&env);
+        } else {
+            DataKey::Stake(user.clone()).set(&env, &position);
+        }
+        StorageKey::PlatformState.set(&env, &state);
+
+        if rewards > 0 {
+            let kale_client = token::Client::new(&env, &state.kale_token);
+            kale_client.transfer(&env.current_contract_address(), &user, &rewards);
+        }
+
+        events::publish(
+            &env,
+            events::REWARDS_CLAIMED,
+            &user,
+            RewardsClaimedEvent {
+                rewards,
+                fee,
+                total_earned: position.total_earned,
+            },
+        );
 
         Ok(rewards)
     }
 
+    // Deposit KALE into the reserve that staking rewards are paid from
+    pub fn fund_reward_pool(env: Env, funder: Address, amount: i128) -> Result<i128, Error> {
+        funder.require_auth();
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        let mut state: PlatformState = StorageKey::PlatformState.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        let kale_client = token::Client::new(&env, &state.kale_token);
+        kale_client.transfer(&funder, &env.current_contract_address(), &amount);
+
+        state.reward_reserve += amount;
+        StorageKey::PlatformState.set(&env, &state);
+
+        events::publish(
+            &env,
+            events::REWARD_POOL_FUNDED,
+            &funder,
+            RewardPoolFundedEvent {
+                amount,
+                reward_reserve: state.reward_reserve,
+            },
+        );
+
+        Ok(state.reward_reserve)
+    }
+
     // Check and adjust staking based on price movements
-    pub fn check_price_adjustments(env: Env, user: Address) -> Result<bool, SorobanError> {
-        let state: PlatformState = env.storage().instance().get(&StorageKey::PlatformState.to_symbol())
-            .ok_or(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ))?;
-
-        let mut staking_positions: Map<Address, StakingPosition> = env.storage().instance().get(&StorageKey::StakingPositions.to_symbol())
-            .unwrap_or(Map::new(&env));
-
-        let mut position = staking_positions.get(user.clone())
-            .ok_or(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ))?;
+    pub fn check_price_adjustments(env: Env, user: Address) -> Result<bool, Error> {
+        user.require_auth();
+
+        let mut state: PlatformState = StorageKey::PlatformState.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        let config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        let mut position: StakingPosition = DataKey::Stake(user.clone()).get(&env)
+            .ok_or(Error::PositionNotFound)?;
 
         if !position.auto_adjust_enabled {
             return Ok(false);
         }
 
-        let current_price = Self::get_kale_price(&env, &state.reflector_oracle)?;
+        let current_price = Self::get_kale_price(&env, &config)?;
         let price_change = if position.last_adjustment_price > 0 {
             ((current_price - position.last_adjustment_price) * 10000) / position.last_adjustment_price
         } else {
@@ -441,18 +1012,43 @@ impl KaleLendingPlatform {
         // If price change exceeds threshold, adjust stake
         if price_change.abs() >= position.price_threshold {
             // Adjust stake based on price movement
-            let adjustment_factor = if price_change > 0 {
-                10000 + (price_change / 10) // Increase stake by 10% of price increase
-            } else {
-                10000 + (price_change / 10) // Decrease stake by 10% of price decrease
-            };
-
-            let new_amount = (position.kale_amount * adjustment_factor) / 10000;
-            position.kale_amount = new_amount;
+            // Increase/decrease stake by 10% of the price movement
+            let adjustment_factor = 10000 + (price_change / 10);
+
+            // Rewards up to now are earned on the old amount
+            Self::accrue_borrow_index(&env, &config, &mut state);
+            Self::update_reward_per_token(&env, &mut state);
+            Self::settle_staking_rewards(&env, &state, &mut position)?;
+
+            // The stake only changes by real KALE: a rise is pulled from the user's wallet
+            // and a fall is paid back to it
+            let previous_amount = position.kale_amount;
+            let target_amount = (previous_amount * adjustment_factor) / 10000;
+            if target_amount > previous_amount {
+                // Topping up is a new deposit, which a paused platform does not take
+                if !state.is_active {
+                    return Err(Error::Paused);
+                }
+                Self::deposit_stake(&env, &mut state, &user, &mut position, target_amount - previous_amount)?;
+            } else if target_amount < previous_amount {
+                Self::withdraw_stake(&env, &mut state, &user, &mut position, previous_amount - target_amount)?;
+            }
+            let new_amount = position.kale_amount;
             position.last_adjustment_price = current_price;
 
-            staking_positions.set(user, position);
-            env.storage().instance().set(&StorageKey::StakingPositions.to_symbol(), &staking_positions);
+            DataKey::Stake(user.clone()).set(&env, &position);
+            StorageKey::PlatformState.set(&env, &state);
+
+            events::publish(
+                &env,
+                events::STAKE_ADJUSTED,
+                &user,
+                StakeAdjustedEvent {
+                    previous_amount,
+                    kale_amount: new_amount,
+                    kale_price: current_price,
+                },
+            );
 
             return Ok(true);
         }
@@ -461,122 +1057,1299 @@ impl KaleLendingPlatform {
     }
 
     // Get current KALE price from Reflector
-    pub fn get_current_kale_price(env: Env) -> Result<i128, SorobanError> {
-        let state: PlatformState = env.storage().instance().get(&StorageKey::PlatformState.to_symbol())
-            .ok_or(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ))?;
+    pub fn get_current_kale_price(env: Env) -> Result<i128, Error> {
+        let config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        Self::get_kale_price(&env, &config)
+    }
+
+    // Diagnostic view of the oracle feed's freshness
+    pub fn get_oracle_status(env: Env) -> Result<OracleStatus, Error> {
+        let config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        // A primary that cannot be reached shows as stale
+        let reflector_client = ReflectorClient::new(&env, &config.reflector_oracle);
+        let last_timestamp = Self::oracle_answer(reflector_client.try_last_timestamp());
+
+        Ok(OracleStatus {
+            oracle: config.reflector_oracle,
+            fallback_oracle: config.fallback_oracle,
+            last_timestamp: last_timestamp.unwrap_or(0),
+            resolution: Self::oracle_answer(reflector_client.try_resolution()).unwrap_or(0),
+            max_price_age: config.max_price_age,
+            is_stale: last_timestamp.is_none_or(|timestamp| {
+                env.ledger().timestamp().saturating_sub(timestamp) > config.max_price_age
+            }),
+        })
+    }
 
-        Self::get_kale_price(&env, &state.reflector_oracle)
+    // Current utilization and the borrow and supply rates it implies
+    pub fn get_interest_rates(env: Env) -> Result<InterestRates, Error> {
+        let state: PlatformState = StorageKey::PlatformState.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        let config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        Ok(Self::interest_rates(&env, &config, &state))
     }
 
     // Get user's staking position
-    pub fn get_staking_position(env: Env, user: Address) -> Result<StakingPosition, SorobanError> {
-        let staking_positions: Map<Address, StakingPosition> = env.storage().instance().get(&StorageKey::StakingPositions.to_symbol())
-            .unwrap_or(Map::new(&env));
+    pub fn get_staking_position(env: Env, user: Address) -> Result<StakingPosition, Error> {
+        let mut position: StakingPosition = DataKey::Stake(user).get(&env)
+            .ok_or(Error::PositionNotFound)?;
+
+        // Report the stake with lender interest accrued up to now
+        if let (Some(mut state), Some(config)) = (
+            StorageKey::PlatformState.get::<PlatformState>(&env),
+            StorageKey::PlatformConfig.get::<PlatformConfig>(&env),
+        ) {
+            Self::accrue_borrow_index(&env, &config, &mut state);
+            position.kale_amount = Self::shares_to_kale(&state, position.shares);
+        }
+
+        Ok(position)
+    }
 
-        staking_positions.get(user)
-            .ok_or(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ))
+    // Break a staker's earnings down into emission rewards and their share of borrow
+    // interest, both accrued up to now
+    pub fn get_staker_earnings(env: Env, user: Address) -> Result<StakerEarnings, Error> {
+        let mut state: PlatformState = StorageKey::PlatformState.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        let config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        let position: StakingPosition = DataKey::Stake(user).get(&env)
+            .ok_or(Error::PositionNotFound)?;
+
+        Self::accrue_borrow_index(&env, &config, &mut state);
+        Self::update_reward_per_token(&env, &mut state);
+        let unsettled_rewards = (position.shares
+            * (state.reward_per_token_stored - position.reward_per_token_paid))
+            / REWARD_SCALE;
+        let unsettled_interest = (position.shares
+            * (state.interest_per_share_stored - position.interest_per_share_paid))
+            / REWARD_SCALE;
+
+        Ok(StakerEarnings {
+            emission_rewards: position.total_earned + unsettled_rewards,
+            fee_share: position.interest_earned + unsettled_interest,
+        })
+    }
+
+    // KALE redeemable for one kKALE, scaled by REWARD_SCALE
+    pub fn get_exchange_rate(env: Env) -> Result<i128, Error> {
+        let mut state: PlatformState = StorageKey::PlatformState.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        let config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        Self::accrue_borrow_index(&env, &config, &mut state);
+        if state.total_shares == 0 {
+            return Ok(REWARD_SCALE);
+        }
+        Ok(((state.total_staked + VIRTUAL_STAKE) * REWARD_SCALE) / (state.total_shares + VIRTUAL_STAKE))
     }
 
     // Get user's borrowing position
-    pub fn get_borrowing_position(env: Env, user: Address) -> Result<BorrowingPosition, SorobanError> {
-        let borrowing_positions: Map<Address, BorrowingPosition> = env.storage().instance().get(&StorageKey::BorrowingPositions.to_symbol())
-            .unwrap_or(Map::new(&env));
+    pub fn get_borrowing_position(env: Env, user: Address) -> Result<BorrowingPosition, Error> {
+        let mut position: BorrowingPosition = DataKey::Borrow(user).get(&env)
+            .ok_or(Error::PositionNotFound)?;
+
+        // Report the debt with interest accrued up to now
+        if let (Some(mut state), Some(config)) = (
+            StorageKey::PlatformState.get::<PlatformState>(&env),
+            StorageKey::PlatformConfig.get::<PlatformConfig>(&env),
+        ) {
+            Self::accrue_borrow_index(&env, &config, &mut state);
+            position.borrowed_amount = Self::scaled_to_debt(position.scaled_debt, state.borrow_index);
+        }
 
-        borrowing_positions.get(user)
-            .ok_or(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ))
+        Ok(position)
     }
 
     // Get platform state
-    pub fn get_platform_state(env: Env) -> Result<PlatformState, SorobanError> {
-        StorageKey::PlatformState.get(&env)
-            .ok_or(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ))
+    pub fn get_platform_state(env: Env) -> Result<PlatformState, Error> {
+        let mut state: PlatformState = StorageKey::PlatformState.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        let config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        Self::accrue_borrow_index(&env, &config, &mut state);
+        Ok(state)
+    }
+
+    // Get the admin-set oracle, pricing, liquidation and rate parameters
+    pub fn get_platform_config(env: Env) -> Result<PlatformConfig, Error> {
+        StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)
     }
 
     // Get yield pool information
-    pub fn get_yield_pool(env: Env) -> Result<YieldPool, SorobanError> {
+    pub fn get_yield_pool(env: Env) -> Result<YieldPool, Error> {
         StorageKey::YieldPool.get(&env)
-            .ok_or(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ))
+            .ok_or(Error::NotInitialized)
+    }
+
+    // Whole days the reward reserve can keep paying the current emission once the rewards
+    // already owed to stakers are set aside; u64::MAX while nothing is being emitted
+    pub fn get_reward_runway(env: Env) -> Result<u64, Error> {
+        let mut state: PlatformState = StorageKey::PlatformState.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        let yield_pool: YieldPool = StorageKey::YieldPool.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        Self::update_reward_per_token(&env, &mut state);
+
+        if state.emission_rate == 0 {
+            return Ok(u64::MAX);
+        }
+
+        let owed = state.total_rewards_emitted - yield_pool.total_rewards_distributed;
+        let available = (state.reward_reserve - owed).max(0);
+        Ok((available / (state.emission_rate * SECONDS_PER_DAY)) as u64)
     }
 
     // Admin function to update platform parameters
     pub fn update_platform_config(
         env: Env,
-        staking_apy: Option<i128>,
+        emission_rate: Option<i128>,
         borrowing_apy: Option<i128>,
         platform_fee_rate: Option<i128>,
+        initial_collateral_ratio: Option<i128>,
         liquidation_threshold: Option<i128>,
         is_active: Option<bool>,
-    ) -> Result<(), SorobanError> {
-        let mut state: PlatformState = env.storage().instance().get(&StorageKey::PlatformState.to_symbol())
-            .ok_or(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ))?;
-
-        // Check admin authorization (simplified for MVP)
-        
-        if let Some(apy) = staking_apy {
-            state.staking_apy = apy;
+    ) -> Result<(), Error> {
+        let mut state: PlatformState = StorageKey::PlatformState.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        let mut config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        config.admin.require_auth();
+
+        // Interest and rewards so far accrue at the old rates
+        Self::accrue_borrow_index(&env, &config, &mut state);
+        Self::update_reward_per_token(&env, &mut state);
+
+        if let Some(rate) = emission_rate {
+            if rate < 0 {
+                return Err(Error::InvalidConfig);
+            }
+            state.emission_rate = rate;
         }
         if let Some(apy) = borrowing_apy {
-            state.borrowing_apy = apy;
+            if apy < 0 {
+                return Err(Error::InvalidConfig);
+            }
+            config.borrowing_apy = apy;
         }
         if let Some(fee) = platform_fee_rate {
+            if !(0..=10000).contains(&fee) {
+                return Err(Error::InvalidConfig);
+            }
             state.platform_fee_rate = fee;
         }
+        if let Some(ratio) = initial_collateral_ratio {
+            config.initial_collateral_ratio = ratio;
+        }
         if let Some(threshold) = liquidation_threshold {
-            state.liquidation_threshold = threshold;
+            config.liquidation_threshold = threshold;
         }
+        Self::validate_collateral_ratios(config.initial_collateral_ratio, config.liquidation_threshold)?;
         if let Some(active) = is_active {
             state.is_active = active;
         }
 
-        env.storage().instance().set(&StorageKey::PlatformState.to_symbol(), &state);
+        StorageKey::PlatformConfig.set(&env, &config);
+        StorageKey::PlatformState.set(&env, &state);
+
+        events::publish(
+            &env,
+            events::CONFIG_UPDATED,
+            &config.admin,
+            ConfigUpdatedEvent {
+                emission_rate: state.emission_rate,
+                borrowing_apy: config.borrowing_apy,
+                platform_fee_rate: state.platform_fee_rate,
+                initial_collateral_ratio: config.initial_collateral_ratio,
+                liquidation_threshold: config.liquidation_threshold,
+                is_active: state.is_active,
+            },
+        );
+
         Ok(())
     }
 
-    // Helper function to get KALE price from Reflector oracle
-    fn get_kale_price(env: &Env, oracle_address: &Address) -> Result<i128, SorobanError> {
-        let reflector_client = ReflectorClient::new(env, oracle_address);
-        let kale_asset = ReflectorAsset::Other(symbol_short!("KALE"));
-        
-        let price_data = reflector_client.lastprice(&kale_asset)
-            .ok_or(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ))?;
-        
-        Ok(price_data.price)
+    // Admin function to update liquidation parameters
+    pub fn update_liquidation_config(
+        env: Env,
+        liquidation_bonus: Option<i128>,
+        close_factor: Option<i128>,
+    ) -> Result<(), Error> {
+        let mut config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        config.admin.require_auth();
+
+        if let Some(bonus) = liquidation_bonus {
+            if !(0..10000).contains(&bonus) {
+                return Err(Error::InvalidConfig);
+            }
+            config.liquidation_bonus = bonus;
+        }
+        if let Some(factor) = close_factor {
+            if factor <= 0 || factor > 10000 {
+                return Err(Error::InvalidConfig);
+            }
+            config.close_factor = factor;
+        }
+
+        StorageKey::PlatformConfig.set(&env, &config);
+
+        events::publish(
+            &env,
+            events::LIQUIDATION_CONFIG_UPDATED,
+            &config.admin,
+            LiquidationConfigUpdatedEvent {
+                liquidation_bonus: config.liquidation_bonus,
+                close_factor: config.close_factor,
+            },
+        );
+
+        Ok(())
+    }
+
+    // Admin function to change the address platform fees are withdrawn to
+    pub fn set_treasury(env: Env, treasury: Address) -> Result<(), Error> {
+        let mut state: PlatformState = StorageKey::PlatformState.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        let config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        config.admin.require_auth();
+
+        state.treasury = treasury.clone();
+        StorageKey::PlatformState.set(&env, &state);
+
+        events::publish(&env, events::TREASURY_SET, &config.admin, TreasurySetEvent { treasury });
+
+        Ok(())
+    }
+
+    // Admin function to send collected platform fees to the treasury
+    pub fn withdraw_fees(env: Env, amount: i128) -> Result<i128, Error> {
+        let mut state: PlatformState = StorageKey::PlatformState.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        let config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        config.admin.require_auth();
+
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        if amount > state.treasury_balance {
+            return Err(Error::InsufficientFees);
+        }
+
+        state.treasury_balance -= amount;
+        StorageKey::PlatformState.set(&env, &state);
+
+        let kale_client = token::Client::new(&env, &state.kale_token);
+        kale_client.transfer(&env.current_contract_address(), &state.treasury, &amount);
+
+        events::publish(
+            &env,
+            events::FEES_WITHDRAWN,
+            &config.admin,
+            FeesWithdrawnEvent {
+                to: state.treasury.clone(),
+                amount,
+                treasury_balance: state.treasury_balance,
+            },
+        );
+
+        Ok(state.treasury_balance)
+    }
+
+    // Admin function to set or clear the fallback oracle
+    pub fn set_fallback_oracle(env: Env, fallback_oracle: Option<Address>) -> Result<(), Error> {
+        let mut config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        config.admin.require_auth();
+
+        if fallback_oracle.as_ref() == Some(&config.reflector_oracle) {
+            return Err(Error::InvalidConfig);
+        }
+        config.fallback_oracle = fallback_oracle;
+
+        StorageKey::PlatformConfig.set(&env, &config);
+
+        events::publish(
+            &env,
+            events::FALLBACK_ORACLE_SET,
+            &config.admin,
+            FallbackOracleSetEvent {
+                fallback_oracle: config.fallback_oracle.clone(),
+            },
+        );
+
+        Ok(())
+    }
+
+    // Admin function to tune the interest rate model. `base_rate` is the rate at zero
+    // utilization and is stored as `borrowing_apy`.
+    pub fn update_rate_model(
+        env: Env,
+        base_rate: Option<i128>,
+        slope1: Option<i128>,
+        optimal_utilization: Option<i128>,
+        slope2: Option<i128>,
+    ) -> Result<(), Error> {
+        let mut state: PlatformState = StorageKey::PlatformState.get(&env)
+            .ok_or(Error::NotInitialized)?;
+        let mut config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        config.admin.require_auth();
+
+        // Interest and supply rewards so far accrue at the old rates
+        Self::accrue_borrow_index(&env, &config, &mut state);
+        Self::update_reward_per_token(&env, &mut state);
+
+        if let Some(rate) = base_rate {
+            config.borrowing_apy = rate;
+        }
+        if let Some(slope) = slope1 {
+            config.rate_slope1 = slope;
+        }
+        if let Some(utilization) = optimal_utilization {
+            config.optimal_utilization = utilization;
+        }
+        if let Some(slope) = slope2 {
+            config.rate_slope2 = slope;
+        }
+
+        if config.borrowing_apy < 0
+            || config.rate_slope1 < 0
+            || config.rate_slope2 < 0
+            || config.optimal_utilization <= 0
+            || config.optimal_utilization >= 10000
+        {
+            return Err(Error::InvalidConfig);
+        }
+
+        StorageKey::PlatformConfig.set(&env, &config);
+        StorageKey::PlatformState.set(&env, &state);
+
+        events::publish(
+            &env,
+            events::RATE_MODEL_UPDATED,
+            &config.admin,
+            RateModelUpdatedEvent {
+                base_rate: config.borrowing_apy,
+                slope1: config.rate_slope1,
+                optimal_utilization: config.optimal_utilization,
+                slope2: config.rate_slope2,
+            },
+        );
+
+        Ok(())
+    }
+
+    // Admin function to change the identifiers the oracles quote KALE and XLM under
+    pub fn set_oracle_assets(env: Env, oracle_assets: OracleAssets) -> Result<(), Error> {
+        let mut config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        config.admin.require_auth();
+
+        Self::validate_oracle_assets(&oracle_assets)?;
+        config.oracle_assets = oracle_assets;
+
+        StorageKey::PlatformConfig.set(&env, &config);
+
+        events::publish(
+            &env,
+            events::ORACLE_ASSETS_SET,
+            &config.admin,
+            OracleAssetsSetEvent {
+                kale: config.oracle_assets.kale.clone(),
+                xlm: config.oracle_assets.xlm.clone(),
+            },
+        );
+
+        Ok(())
+    }
+
+    // Admin function to update the oracle circuit breaker thresholds
+    pub fn update_circuit_breaker_config(
+        env: Env,
+        max_oracle_deviation: Option<i128>,
+        max_price_move: Option<i128>,
+    ) -> Result<(), Error> {
+        let mut config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        config.admin.require_auth();
+
+        if let Some(deviation) = max_oracle_deviation {
+            if !(0..=10000).contains(&deviation) {
+                return Err(Error::InvalidConfig);
+            }
+            config.max_oracle_deviation = deviation;
+        }
+        if let Some(price_move) = max_price_move {
+            if !(0..=10000).contains(&price_move) {
+                return Err(Error::InvalidConfig);
+            }
+            config.max_price_move = price_move;
+        }
+
+        StorageKey::PlatformConfig.set(&env, &config);
+
+        events::publish(
+            &env,
+            events::CIRCUIT_BREAKER_CONFIG_UPDATED,
+            &config.admin,
+            CircuitBreakerConfigUpdatedEvent {
+                max_oracle_deviation: config.max_oracle_deviation,
+                max_price_move: config.max_price_move,
+            },
+        );
+
+        Ok(())
+    }
+
+    // Admin function to update oracle parameters
+    pub fn update_oracle_config(
+        env: Env,
+        max_price_age: Option<u64>,
+        pricing_mode: Option<PricingMode>,
+        use_cross_price: Option<bool>,
+        cross_price_tolerance: Option<i128>,
+    ) -> Result<(), Error> {
+        let mut config: PlatformConfig = StorageKey::PlatformConfig.get(&env)
+            .ok_or(Error::NotInitialized)?;
+
+        config.admin.require_auth();
+
+        if let Some(age) = max_price_age {
+            if age == 0 {
+                return Err(Error::InvalidConfig);
+            }
+            config.max_price_age = age;
+        }
+
+        if let Some(mode) = pricing_mode {
+            if matches!(mode, PricingMode::Twap(0) | PricingMode::Conservative(0)) {
+                return Err(Error::InvalidConfig);
+            }
+            config.pricing_mode = mode;
+        }
+
+        if let Some(tolerance) = cross_price_tolerance {
+            if tolerance <= 0 || tolerance > 10000 {
+                return Err(Error::InvalidConfig);
+            }
+            config.cross_price_tolerance = tolerance;
+        }
+
+        if let Some(enabled) = use_cross_price {
+            config.use_cross_price = enabled;
+        }
+
+        StorageKey::PlatformConfig.set(&env, &config);
+
+        events::publish(
+            &env,
+            events::ORACLE_CONFIG_UPDATED,
+            &config.admin,
+            OracleConfigUpdatedEvent {
+                max_price_age: config.max_price_age,
+                pricing_mode: config.pricing_mode.clone(),
+                use_cross_price: config.use_cross_price,
+                cross_price_tolerance: config.cross_price_tolerance,
+            },
+        );
+
+        Ok(())
+    }
+
+    // Admin function to move positions out of the legacy instance-storage maps into
+    // per-user persistent entries. Moves up to `limit` entries of each map per call and
+    // returns how many were moved; call again until it returns 0. The first call also
+    // splits a legacy platform state into the current config and state.
+    pub fn migrate_positions(env: Env, limit: u32) -> Result<u32, Error> {
+        // Instances from before the config split keep everything in one legacy struct
+        let (config, mut state) = match StorageKey::PlatformConfig.get::<PlatformConfig>(&env) {
+            Some(config) => {
+                let state: PlatformState = StorageKey::PlatformState.get(&env)
+                    .ok_or(Error::NotInitialized)?;
+                (config, state)
+            }
+            None => {
+                let legacy: LegacyPlatformState = StorageKey::PlatformState.get(&env)
+                    .ok_or(Error::NotInitialized)?;
+                Self::migrate_platform_state(&env, legacy)
+            }
+        };
+
+        config.admin.require_auth();
+
+        // Minting shares and scaled debt happens at the current index and reward rate
+        Self::accrue_borrow_index(&env, &config, &mut state);
+        Self::update_reward_per_token(&env, &mut state);
+
+        // Legacy stakes are counted in total_staked but have no shares yet, so they are left
+        // out of the exchange rate until each one is migrated
+        state.total_staked -= Self::legacy_staked(&env);
+
+        let migrated_stakes = Self::migrate_position_map(
+            &env,
+            &mut state,
+            StorageKey::StakingPositions,
+            DataKey::Stake,
+            Self::migrate_staking_position,
+            limit,
+        );
+        let migrated_borrows = Self::migrate_position_map(
+            &env,
+            &mut state,
+            StorageKey::BorrowingPositions,
+            DataKey::Borrow,
+            Self::migrate_borrowing_position,
+            limit,
+        );
+        state.total_staked += Self::legacy_staked(&env);
+        state.total_borrowed = Self::scaled_to_debt(state.total_scaled_debt, state.borrow_index);
+
+        StorageKey::PlatformConfig.set(&env, &config);
+        StorageKey::PlatformState.set(&env, &state);
+
+        let migrated = migrated_stakes + migrated_borrows;
+        events::publish(
+            &env,
+            events::POSITIONS_MIGRATED,
+            &config.admin,
+            PositionsMigratedEvent { migrated },
+        );
+
+        Ok(migrated)
+    }
+
+    // Helper function to split a legacy platform state into the current config and state.
+    // Loans used to open at the liquidation threshold, which stays their opening ratio.
+    // The legacy staking APY has no emission equivalent, so emission starts at zero until
+    // the admin sets a rate and funds the reward pool.
+    fn migrate_platform_state(env: &Env, legacy: LegacyPlatformState) -> (PlatformConfig, PlatformState) {
+        let config = PlatformConfig {
+            admin: legacy.admin.clone(),
+            reflector_oracle: legacy.reflector_oracle,
+            fallback_oracle: None,
+            oracle_assets: Self::default_oracle_assets(),
+            max_price_age: DEFAULT_MAX_PRICE_AGE,
+            pricing_mode: PricingMode::Spot,
+            use_cross_price: false,
+            cross_price_tolerance: DEFAULT_CROSS_PRICE_TOLERANCE,
+            max_oracle_deviation: DEFAULT_MAX_ORACLE_DEVIATION,
+            max_price_move: 0,
+            initial_collateral_ratio: legacy.liquidation_threshold,
+            liquidation_threshold: legacy.liquidation_threshold,
+            liquidation_bonus: DEFAULT_LIQUIDATION_BONUS,
+            close_factor: DEFAULT_CLOSE_FACTOR,
+            borrowing_apy: legacy.borrowing_apy,
+            rate_slope1: 0,
+            optimal_utilization: DEFAULT_OPTIMAL_UTILIZATION,
+            rate_slope2: 0,
+        };
+
+        // Debt is rebuilt from the legacy loans as they migrate
+        let state = PlatformState {
+            kale_token: legacy.kale_token,
+            xlm_token: legacy.xlm_token,
+            total_staked: legacy.total_staked,
+            total_shares: 0,
+            total_borrowed: 0,
+            total_scaled_debt: 0,
+            borrow_index: INDEX_SCALE,
+            last_accrual_time: env.ledger().timestamp(),
+            total_collateral: legacy.total_collateral,
+            emission_rate: 0,
+            reward_per_token_stored: 0,
+            interest_per_share_stored: 0,
+            last_reward_update: env.ledger().timestamp(),
+            total_rewards_emitted: 0,
+            reward_reserve: 0,
+            accrued_fees: 0,
+            treasury: legacy.admin,
+            treasury_balance: 0,
+            current_kale_price: legacy.current_kale_price,
+            current_xlm_price: legacy.current_xlm_price,
+            last_price_update: legacy.last_price_update,
+            platform_fee_rate: legacy.platform_fee_rate,
+            is_active: legacy.is_active,
+        };
+
+        (config, state)
+    }
+
+    // Helper function to drain up to `limit` entries of a legacy position map, converting
+    // each to the current layout; entries `convert` rejects are dropped
+    fn migrate_position_map<L, P>(
+        env: &Env,
+        state: &mut PlatformState,
+        legacy_key: StorageKey,
+        data_key: fn(Address) -> DataKey,
+        convert: fn(&Env, &mut PlatformState, L) -> Option<P>,
+        limit: u32,
+    ) -> u32
+    where
+        L: soroban_sdk::IntoVal<Env, soroban_sdk::Val> + soroban_sdk::TryFromVal<Env, soroban_sdk::Val>,
+        P: soroban_sdk::IntoVal<Env, soroban_sdk::Val> + soroban_sdk::TryFromVal<Env, soroban_sdk::Val>,
+    {
+        let mut positions: Map<Address, L> = match legacy_key.get(env) {
+            Some(positions) => positions,
+            None => return 0,
+        };
+
+        let mut migrated = 0;
+        for user in positions.keys().iter().take(limit as usize) {
+            let key = data_key(user.clone());
+            // Never overwrite a position that was already written under the new layout
+            if !key.has(env) {
+                if let Some(position) = positions.get(user.clone()).and_then(|legacy| convert(env, state, legacy)) {
+                    key.set(env, &position);
+                }
+            }
+            positions.remove(user);
+            migrated += 1;
+        }
+
+        if positions.is_empty() {
+            legacy_key.remove(env);
+        } else {
+            legacy_key.set(env, &positions);
+        }
+
+        migrated
+    }
+
+    // Helper function to total the KALE of the stakes still in the legacy map
+    fn legacy_staked(env: &Env) -> i128 {
+        StorageKey::StakingPositions
+            .get::<Map<Address, LegacyStakingPosition>>(env)
+            .map(|positions| positions.values().iter().map(|position| position.kale_amount).sum())
+            .unwrap_or(0)
+    }
+
+    // Helper function to mint kKALE for a legacy stake at the exchange rate of the
+    // migrated pool
+    fn migrate_staking_position(env: &Env, state: &mut PlatformState, legacy: LegacyStakingPosition) -> Option<StakingPosition> {
+        let mut position = Self::new_staking_position(env, state, &legacy.user, legacy.last_adjustment_price);
+        position.start_time = legacy.start_time;
+        position.last_claim_time = legacy.last_claim_time;
+        position.auto_adjust_enabled = legacy.auto_adjust_enabled;
+        position.price_threshold = legacy.price_threshold;
+        position.total_earned = legacy.total_earned;
+
+        position.shares = Self::kale_to_shares(state, legacy.kale_amount, false);
+        state.total_staked += legacy.kale_amount;
+        state.total_shares += position.shares;
+        position.kale_amount = Self::shares_to_kale(state, position.shares);
+
+        events::publish_mint(env, &legacy.user, position.shares);
+        Some(position)
+    }
+
+    // Helper function to record a legacy loan's debt as scaled debt at the current index.
+    // Closed loans are dropped: legacy repayments could overshoot into a negative balance,
+    // which must not be carried over as debt.
+    fn migrate_borrowing_position(_env: &Env, state: &mut PlatformState, legacy: LegacyBorrowingPosition) -> Option<BorrowingPosition> {
+        if !legacy.is_active || legacy.borrowed_amount <= 0 {
+            return None;
+        }
+
+        let scaled_debt = Self::debt_to_scaled(legacy.borrowed_amount, state.borrow_index);
+        state.total_scaled_debt += scaled_debt;
+        Some(BorrowingPosition {
+            user: legacy.user,
+            borrowed_amount: legacy.borrowed_amount,
+            principal: legacy.borrowed_amount,
+            scaled_debt,
+            collateral_amount: legacy.collateral_amount,
+            borrow_time: legacy.borrow_time,
+            interest_rate: legacy.interest_rate,
+            last_payment_time: legacy.last_payment_time,
+            total_interest_paid: legacy.total_interest_paid,
+            is_active: true,
+        })
+    }
+
+    // Helper function to ensure loans open with a safety margin above the liquidation point
+    fn validate_collateral_ratios(initial_collateral_ratio: i128, liquidation_threshold: i128) -> Result<(), Error> {
+        if liquidation_threshold < 10000 || initial_collateral_ratio < liquidation_threshold {
+            return Err(Error::InvalidConfig);
+        }
+        Ok(())
+    }
+
+    // Helper function to get the identifiers the oracles quote KALE and XLM under by default
+    fn default_oracle_assets() -> OracleAssets {
+        OracleAssets {
+            kale: ReflectorAsset::Other(symbol_short!("KALE")),
+            xlm: ReflectorAsset::Other(symbol_short!("XLM")),
+        }
+    }
+
+    // Helper function to reject quoting both tokens under one identifier
+    fn validate_oracle_assets(oracle_assets: &OracleAssets) -> Result<(), Error> {
+        if oracle_assets.kale == oracle_assets.xlm {
+            return Err(Error::InvalidConfig);
+        }
+        Ok(())
+    }
+
+    // Helper function to open an empty staking position with default settings
+    fn new_staking_position(env: &Env, state: &PlatformState, user: &Address, price: i128) -> StakingPosition {
+        StakingPosition {
+            user: user.clone(),
+            kale_amount: 0,
+            shares: 0,
+            start_time: env.ledger().timestamp(),
+            last_claim_time: env.ledger().timestamp(),
+            auto_adjust_enabled: false,
+            price_threshold: DEFAULT_PRICE_THRESHOLD,
+            last_adjustment_price: price,
+            total_earned: 0,
+            interest_earned: 0,
+            reward_per_token_paid: state.reward_per_token_stored,
+            interest_per_share_paid: state.interest_per_share_stored,
+            pending_rewards: 0,
+        }
+    }
+
+    // Helper function to escrow `amount` more KALE from the user in their position, minting
+    // kKALE for it at the current exchange rate
+    fn deposit_stake(
+        env: &Env,
+        state: &mut PlatformState,
+        user: &Address,
+        position: &mut StakingPosition,
+        amount: i128,
+    ) -> Result<i128, Error> {
+        // Round in the pool's favour, but refuse a stake that would lose more than
+        // MAX_STAKE_ROUNDING_LOSS of its value to rounding
+        let shares = Self::kale_to_shares(state, amount, false);
+        let value = if state.total_shares == 0 {
+            shares
+        } else {
+            Self::shares_to_kale(state, shares)
+        };
+        if shares <= 0 || value * 10000 < amount * (10000 - MAX_STAKE_ROUNDING_LOSS) {
+            return Err(Error::InvalidAmount);
+        }
+
+        let kale_client = token::Client::new(env, &state.kale_token);
+        kale_client.transfer(user, &env.current_contract_address(), &amount);
+
+        position.shares += shares;
+        state.total_shares += shares;
+        state.total_staked += amount;
+        position.kale_amount = Self::shares_to_kale(state, position.shares);
+
+        events::publish_mint(env, user, shares);
+        Ok(shares)
+    }
+
+    // Helper function to pay `amount` of a position's KALE back to the user, burning the
+    // kKALE behind it. Positions must be settled first so their value is current.
+    fn withdraw_stake(
+        env: &Env,
+        state: &mut PlatformState,
+        user: &Address,
+        position: &mut StakingPosition,
+        amount: i128,
+    ) -> Result<i128, Error> {
+        if amount > position.kale_amount {
+            return Err(Error::InsufficientStake);
+        }
+        // KALE currently lent to borrowers can only be withdrawn once repaid
+        if amount > Self::available_liquidity(env, state) {
+            return Err(Error::InsufficientLiquidity);
+        }
+
+        // Burn the shares backing the withdrawal, rounding in the pool's favour
+        let shares = if amount == position.kale_amount {
+            position.shares
+        } else {
+            Self::kale_to_shares(state, amount, true)
+        };
+        Self::redeem_shares(env, state, user, position, shares, amount);
+        Ok(shares)
+    }
+
+    // Helper function to burn `shares` from a position and pay out the `amount` of KALE
+    // they redeem for
+    fn redeem_shares(
+        env: &Env,
+        state: &mut PlatformState,
+        user: &Address,
+        position: &mut StakingPosition,
+        shares: i128,
+        amount: i128,
+    ) {
+        position.shares -= shares;
+        state.total_shares -= shares;
+        state.total_staked -= amount;
+        position.kale_amount = Self::shares_to_kale(state, position.shares);
+
+        let kale_client = token::Client::new(env, &state.kale_token);
+        kale_client.transfer(&env.current_contract_address(), user, &amount);
+
+        events::publish_burn(env, user, shares);
+    }
+
+    // Helper function to convert kKALE shares to the KALE they redeem for, rounded down
+    fn shares_to_kale(state: &PlatformState, shares: i128) -> i128 {
+        if state.total_shares == 0 {
+            return 0;
+        }
+        (shares * (state.total_staked + VIRTUAL_STAKE)) / (state.total_shares + VIRTUAL_STAKE)
+    }
+
+    // Helper function to convert KALE to kKALE shares, 1:1 while the pool is empty
+    fn kale_to_shares(state: &PlatformState, amount: i128, round_up: bool) -> i128 {
+        // The first staker mints 1:1 and also picks up any rounding dust left in the pool
+        if state.total_shares == 0 {
+            return amount;
+        }
+        let staked = state.total_staked + VIRTUAL_STAKE;
+        let scaled = amount * (state.total_shares + VIRTUAL_STAKE);
+        if round_up {
+            (scaled + staked - 1) / staked
+        } else {
+            scaled / staked
+        }
+    }
+
+    // Helper function to accrue a staker's pending rewards into their position
+    // (`state` must already be brought up to date with update_reward_per_token)
+    fn settle_staking_rewards(
+        env: &Env,
+        state: &PlatformState,
+        position: &mut StakingPosition,
+    ) -> Result<i128, Error> {
+        // The position earns its share of everything accumulated since it last settled
+        let rewards = (position.shares
+            * (state.reward_per_token_stored - position.reward_per_token_paid))
+            / REWARD_SCALE;
+
+        // Update position; lender interest has already grown the value of its shares, so
+        // only the position's share of it is recorded
+        position.interest_earned += (position.shares
+            * (state.interest_per_share_stored - position.interest_per_share_paid))
+            / REWARD_SCALE;
+        position.kale_amount = Self::shares_to_kale(state, position.shares);
+        position.reward_per_token_paid = state.reward_per_token_stored;
+        position.interest_per_share_paid = state.interest_per_share_stored;
+        position.last_claim_time = env.ledger().timestamp();
+        position.pending_rewards += rewards;
+        position.total_earned += rewards;
+
+        // Update yield pool; the rewards are only distributed once claimed
+        let mut yield_pool: YieldPool = StorageKey::YieldPool.get(env)
+            .ok_or(Error::NotInitialized)?;
+
+        yield_pool.staking_rewards += rewards;
+        StorageKey::YieldPool.set(env, &yield_pool);
+
+        Ok(rewards)
+    }
+
+    // Helper function to advance the staking reward accumulator to now. The emission since
+    // the last update is split over total_shares; nothing accrues while nothing is staked.
+    fn update_reward_per_token(env: &Env, state: &mut PlatformState) {
+        let now = env.ledger().timestamp();
+        if now <= state.last_reward_update {
+            return;
+        }
+
+        if state.total_shares > 0 {
+            let elapsed = (now - state.last_reward_update) as i128;
+            state.reward_per_token_stored += (state.emission_rate * elapsed * REWARD_SCALE) / state.total_shares;
+            state.total_rewards_emitted += state.emission_rate * elapsed;
+        }
+        state.last_reward_update = now;
+    }
+
+    // Helper function to compound the global borrow index up to now at the current borrow
+    // rate, and refresh total_borrowed from the aggregate scaled debt. The interest accrued
+    // goes to stakers, who fund the loans, less the platform fee, raising the kKALE
+    // exchange rate; with nobody staked it is all kept as fees.
+    fn accrue_borrow_index(env: &Env, config: &PlatformConfig, state: &mut PlatformState) {
+        let now = env.ledger().timestamp();
+        if now > state.last_accrual_time {
+            let elapsed = (now - state.last_accrual_time) as i128;
+            let rate = Self::interest_rates(env, config, state).borrow_rate;
+            let previous_debt = Self::scaled_to_debt(state.total_scaled_debt, state.borrow_index);
+            state.borrow_index += (state.borrow_index * rate * elapsed) / (SECONDS_PER_YEAR * 10000);
+            state.last_accrual_time = now;
+
+            let interest = Self::scaled_to_debt(state.total_scaled_debt, state.borrow_index) - previous_debt;
+            let mut fee = (interest * state.platform_fee_rate) / 10000;
+            if state.total_shares > 0 {
+                state.total_staked += interest - fee;
+                state.interest_per_share_stored += ((interest - fee) * REWARD_SCALE) / state.total_shares;
+            } else {
+                fee = interest;
+            }
+            state.accrued_fees += fee;
+        }
+
+        state.total_borrowed = Self::scaled_to_debt(state.total_scaled_debt, state.borrow_index);
+    }
+
+    // Helper function to close a loan whose collateral is gone. The debt left can never be
+    // repaid, so it comes out of the stakers' pool; returns the amount written off.
+    fn write_off_debt(state: &mut PlatformState, position: &mut BorrowingPosition) -> i128 {
+        let bad_debt = Self::scaled_to_debt(position.scaled_debt, state.borrow_index);
+
+        state.total_scaled_debt -= position.scaled_debt;
+        state.total_borrowed = Self::scaled_to_debt(state.total_scaled_debt, state.borrow_index);
+        state.total_staked = (state.total_staked - bad_debt).max(0);
+
+        position.scaled_debt = 0;
+        position.borrowed_amount = 0;
+        position.principal = 0;
+        position.is_active = false;
+
+        bad_debt
+    }
+
+    // Helper function to apply a repayment of at most the position's current debt. Interest
+    // is paid before principal; returns the interest portion.
+    fn apply_repayment(state: &mut PlatformState, position: &mut BorrowingPosition, amount: i128) -> i128 {
+        let interest_paid = (position.borrowed_amount - position.principal).clamp(0, amount);
+        position.principal -= amount - interest_paid;
+        position.total_interest_paid += interest_paid;
+
+        // Clearing the debt removes the whole scaled balance, so no dust is left behind
+        let scaled_amount = if amount >= position.borrowed_amount {
+            position.scaled_debt
+        } else {
+            Self::debt_to_scaled(amount, state.borrow_index).min(position.scaled_debt)
+        };
+        position.scaled_debt -= scaled_amount;
+        position.borrowed_amount = Self::scaled_to_debt(position.scaled_debt, state.borrow_index);
+        if position.scaled_debt == 0 {
+            position.principal = 0;
+        }
+
+        state.total_scaled_debt -= scaled_amount;
+        state.total_borrowed = Self::scaled_to_debt(state.total_scaled_debt, state.borrow_index);
+
+        interest_paid
+    }
+
+    // Helper function to split interest a borrower has just paid between the platform's fee
+    // and the stakers' share. Once no debt is left, whatever fee was still outstanding has
+    // been paid too.
+    fn collect_interest_fee(env: &Env, state: &mut PlatformState, interest_paid: i128) -> Result<(), Error> {
+        let fee = if state.total_scaled_debt == 0 {
+            state.accrued_fees
+        } else {
+            ((interest_paid * state.platform_fee_rate) / 10000).min(state.accrued_fees)
+        };
+        state.accrued_fees -= fee;
+
+        // The rest is the stakers' share, which has already raised the kKALE exchange rate
+        let mut yield_pool: YieldPool = StorageKey::YieldPool.get(env)
+            .ok_or(Error::NotInitialized)?;
+        yield_pool.borrowing_fees += (interest_paid - fee).max(0);
+        StorageKey::YieldPool.set(env, &yield_pool);
+
+        Self::collect_fees(env, state, fee)
+    }
+
+    // Helper function to move fees the contract has received into the treasury balance
+    fn collect_fees(env: &Env, state: &mut PlatformState, fee: i128) -> Result<(), Error> {
+        if fee <= 0 {
+            return Ok(());
+        }
+
+        let mut yield_pool: YieldPool = StorageKey::YieldPool.get(env)
+            .ok_or(Error::NotInitialized)?;
+        yield_pool.platform_fees += fee;
+        StorageKey::YieldPool.set(env, &yield_pool);
+
+        state.treasury_balance += fee;
+        Ok(())
+    }
+
+    // Helper function to convert a scaled balance to KALE at `index`, rounding half up
+    fn scaled_to_debt(scaled: i128, index: i128) -> i128 {
+        (scaled * index + INDEX_SCALE / 2) / INDEX_SCALE
+    }
+
+    // Helper function to convert a KALE amount to a scaled balance at `index`, rounding half up
+    fn debt_to_scaled(amount: i128, index: i128) -> i128 {
+        (amount * INDEX_SCALE + index / 2) / index
+    }
+
+    // Helper function to evaluate the kinked rate model at the current utilization. Lendable
+    // KALE is what is out on loan plus what the contract holds.
+    fn interest_rates(env: &Env, config: &PlatformConfig, state: &PlatformState) -> InterestRates {
+        let liquidity = state.total_borrowed + Self::available_liquidity(env, state);
+        let utilization = if state.total_borrowed <= 0 || liquidity <= 0 {
+            0
+        } else {
+            ((state.total_borrowed * 10000) / liquidity).min(10000)
+        };
+
+        let borrow_rate = if utilization <= config.optimal_utilization {
+            config.borrowing_apy + (config.rate_slope1 * utilization) / config.optimal_utilization
+        } else {
+            config.borrowing_apy
+                + config.rate_slope1
+                + (config.rate_slope2 * (utilization - config.optimal_utilization))
+                    / (10000 - config.optimal_utilization)
+        };
+
+        let supply_rate = (borrow_rate * utilization * (10000 - state.platform_fee_rate))
+            / (10000 * 10000);
+
+        InterestRates {
+            utilization,
+            borrow_rate,
+            supply_rate,
+        }
+    }
+
+    // Helper function to get the KALE held by the contract that can be lent out or
+    // unstaked: everything except the reward reserve and the collected platform fees
+    fn available_liquidity(env: &Env, state: &PlatformState) -> i128 {
+        let balance = token::Client::new(env, &state.kale_token).balance(&env.current_contract_address());
+        (balance - state.reward_reserve - state.treasury_balance).max(0)
+    }
+
+    // Helper function to get KALE price from Reflector oracle
+    fn get_kale_price(env: &Env, config: &PlatformConfig) -> Result<i128, Error> {
+        Self::get_mode_price(env, config, config.oracle_assets.kale.clone(), true)
     }
 
     // Helper function to get XLM price from Reflector oracle
-    fn get_xlm_price(env: &Env, oracle_address: &Address) -> Result<i128, SorobanError> {
-        let reflector_client = ReflectorClient::new(env, oracle_address);
-        let xlm_asset = ReflectorAsset::Other(symbol_short!("XLM"));
-        
-        let price_data = reflector_client.lastprice(&xlm_asset)
-            .ok_or(SorobanError::from_type_and_code(
-                soroban_sdk::xdr::ScErrorType::Context,
-                soroban_sdk::xdr::ScErrorCode::InvalidInput,
-            ))?;
-        
+    fn get_xlm_price(env: &Env, config: &PlatformConfig) -> Result<i128, Error> {
+        Self::get_mode_price(env, config, config.oracle_assets.xlm.clone(), false)
+    }
+
+    // Helper function to apply the platform pricing mode. The spot quote is always read so
+    // staleness is checked; `prefer_higher` picks the side taken in conservative mode.
+    fn get_mode_price(
+        env: &Env,
+        config: &PlatformConfig,
+        asset: ReflectorAsset,
+        prefer_higher: bool,
+    ) -> Result<i128, Error> {
+        let spot = Self::get_oracle_price(env, config, asset.clone())?;
+
+        match config.pricing_mode {
+            PricingMode::Spot => Ok(spot),
+            PricingMode::Twap(records) => Self::get_twap_price(env, config, asset, records),
+            PricingMode::Conservative(records) => {
+                let twap = Self::get_twap_price(env, config, asset, records)?;
+                Ok(if prefer_higher { spot.max(twap) } else { spot.min(twap) })
+            }
+        }
+    }
+
+    // Helper function to pick the quote positions are valued at, given the mode prices
+    // already read for each token
+    fn get_quote(
+        env: &Env,
+        config: &PlatformConfig,
+        valuation: &Valuation,
+        kale_price: i128,
+        xlm_price: i128,
+    ) -> Result<Quote, Error> {
+        if !config.use_cross_price {
+            return Ok(Quote::Usd { kale_price, xlm_price });
+        }
+
+        Ok(Quote::Cross(Self::get_cross_price(env, config, valuation)?))
+    }
+
+    // Helper function to read KALE quoted in XLM under the pricing mode. The spot cross
+    // quote must agree with the ratio of the two USD spot quotes within the tolerance.
+    fn get_cross_price(env: &Env, config: &PlatformConfig, valuation: &Valuation) -> Result<i128, Error> {
+        let kale = config.oracle_assets.kale.clone();
+        let xlm = config.oracle_assets.xlm.clone();
+
+        let spot = Self::read_oracle(env, config, |client| {
+            let price_data = Self::oracle_answer(client.try_x_last_price(&kale, &xlm))
+                .flatten()
+                .ok_or(Error::OracleUnavailable)?;
+            Self::check_quote(env, config, &price_data)
+        })?;
+
+        let kale_spot = Self::get_oracle_price(env, config, kale.clone())?;
+        let xlm_spot = Self::get_oracle_price(env, config, xlm.clone())?;
+        let implied = kale_spot
+            .checked_mul(10i128.pow(valuation.oracle_decimals))
+            .ok_or(Error::MathOverflow)?
+            / xlm_spot;
+        if implied <= 0 || Self::price_deviation(spot, implied) > config.cross_price_tolerance {
+            return Err(Error::PriceDeviation);
+        }
+
+        let twap = |records: u32| {
+            Self::read_oracle(env, config, |client| {
+                match Self::oracle_answer(client.try_x_twap(&kale, &xlm, &records)).flatten() {
+                    Some(price) if price > 0 => Ok(price),
+                    _ => Err(Error::OracleUnavailable),
+                }
+            })
+        };
+
+        // KALE is the debt side, so conservative mode takes the higher cross quote
+        match config.pricing_mode {
+            PricingMode::Spot => Ok(spot),
+            PricingMode::Twap(records) => twap(records),
+            PricingMode::Conservative(records) => Ok(spot.max(twap(records)?)),
+        }
+    }
+
+    // Helper function to read a positive TWAP over the last `records` quotes
+    fn get_twap_price(env: &Env, config: &PlatformConfig, asset: ReflectorAsset, records: u32) -> Result<i128, Error> {
+        Self::read_oracle(env, config, |client| {
+            match Self::oracle_answer(client.try_twap(&asset, &records)).flatten() {
+                Some(price) if price > 0 => Ok(price),
+                _ => Err(Error::OracleUnavailable),
+            }
+        })
+    }
+
+    // Helper function to read a positive quote no older than `max_price_age`
+    fn get_oracle_price(env: &Env, config: &PlatformConfig, asset: ReflectorAsset) -> Result<i128, Error> {
+        Self::read_oracle(env, config, |client| {
+            let price_data = Self::oracle_answer(client.try_lastprice(&asset))
+                .flatten()
+                .ok_or(Error::OracleUnavailable)?;
+            Self::check_quote(env, config, &price_data)
+        })
+    }
+
+    // Helper function to run `read` against the primary oracle, retrying against the
+    // fallback oracle when one is set and the primary fails or cannot be reached. Prices
+    // are rescaled from the decimals of whichever oracle answered to USD_DECIMALS.
+    fn read_oracle<F>(env: &Env, config: &PlatformConfig, read: F) -> Result<i128, Error>
+    where
+        F: Fn(&ReflectorClient) -> Result<i128, Error>,
+    {
+        let primary_client = ReflectorClient::new(env, &config.reflector_oracle);
+        let error = match Self::read_price(&primary_client, &read) {
+            Ok(price) => return Ok(price),
+            Err(error) => error,
+        };
+
+        let fallback_oracle = config.fallback_oracle.as_ref().ok_or(error)?;
+        let fallback_client = ReflectorClient::new(env, fallback_oracle);
+        Self::read_price(&fallback_client, &read).map_err(|_| error)
+    }
+
+    // Helper function to run `read` against one oracle and rescale its price to USD_DECIMALS
+    fn read_price<F>(client: &ReflectorClient, read: &F) -> Result<i128, Error>
+    where
+        F: Fn(&ReflectorClient) -> Result<i128, Error>,
+    {
+        let decimals = Self::oracle_answer(client.try_decimals()).ok_or(Error::OracleUnavailable)?;
+        let price = read(client)?;
+        valuation::rescale(price, USD_DECIMALS as i32 - decimals as i32)
+    }
+
+    // Helper function to unwrap a `try_*` oracle call, or None when the oracle could not
+    // be reached or answered with something that does not decode
+    fn oracle_answer<T, E, F>(result: Result<Result<T, E>, F>) -> Option<T> {
+        result.ok()?.ok()
+    }
+
+    // Helper function to accept a positive quote no older than `max_price_age`
+    fn check_quote(env: &Env, config: &PlatformConfig, price_data: &PriceData) -> Result<i128, Error> {
+        if price_data.price <= 0 {
+            return Err(Error::OracleUnavailable);
+        }
+        if env.ledger().timestamp().saturating_sub(price_data.timestamp) > config.max_price_age {
+            return Err(Error::StalePrice);
+        }
+
         Ok(price_data.price)
     }
+
+    // Helper function to refuse borrowing and liquidation while the feeds look unreliable:
+    // the two oracles disagree by more than `max_oracle_deviation`, or a quote moved more
+    // than `max_price_move` from the previous tick. Zero disables either check.
+    fn check_circuit_breaker(env: &Env, config: &PlatformConfig) -> Result<(), Error> {
+        let primary_client = ReflectorClient::new(env, &config.reflector_oracle);
+        let fallback_client = config.fallback_oracle.as_ref()
+            .map(|oracle| ReflectorClient::new(env, oracle));
+
+        for asset in [&config.oracle_assets.kale, &config.oracle_assets.xlm] {
+            let primary = Self::check_price_move(env, config, &primary_client, asset)?;
+
+            if let Some(fallback_client) = &fallback_client {
+                let fallback = Self::check_price_move(env, config, fallback_client, asset)?;
+
+                if let (Some(primary), Some(fallback)) = (primary, fallback) {
+                    if config.max_oracle_deviation > 0
+                        && Self::price_deviation(fallback, primary) > config.max_oracle_deviation
+                    {
+                        return Err(Error::CircuitBreakerTripped);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Helper function to return an oracle's latest usable quote for `asset` at USD_DECIMALS,
+    // failing when it moved more than `max_price_move` from the tick before. An oracle that
+    // cannot be reached has no quote and is skipped.
+    fn check_price_move(
+        env: &Env,
+        config: &PlatformConfig,
+        client: &ReflectorClient,
+        asset: &ReflectorAsset,
+    ) -> Result<Option<i128>, Error> {
+        let Some(decimals) = Self::oracle_answer(client.try_decimals()) else {
+            return Ok(None);
+        };
+        let Some(records) = Self::oracle_answer(client.try_prices(asset, &2)).flatten() else {
+            return Ok(None);
+        };
+        let Some(latest) = records.get(0) else {
+            return Ok(None);
+        };
+
+        if let Some(previous) = records.get(1) {
+            if config.max_price_move > 0
+                && previous.price > 0
+                && Self::price_deviation(latest.price, previous.price) > config.max_price_move
+            {
+                return Err(Error::CircuitBreakerTripped);
+            }
+        }
+
+        match Self::check_quote(env, config, &latest) {
+            Ok(price) => Ok(Some(valuation::rescale(price, USD_DECIMALS as i32 - decimals as i32)?)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    // Helper function to compute how far `price` is from `reference`, in basis points
+    fn price_deviation(price: i128, reference: i128) -> i128 {
+        ((price - reference).abs() * 10000) / reference
+    }
 }
 
 mod test;
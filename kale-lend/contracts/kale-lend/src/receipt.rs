@@ -0,0 +1,212 @@
+use soroban_sdk::token::{self, TokenInterface};
+use soroban_sdk::{contractimpl, contracttype, panic_with_error, Address, Env, String};
+
+use crate::{
+    events, DataKey, Error, KaleLendingPlatform, KaleLendingPlatformClient, PlatformConfig, PlatformState,
+    StakingPosition, StorageKey,
+};
+
+// Allowance granted by a kKALE holder to a spender
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AllowanceValue {
+    pub amount: i128,
+    pub expiration_ledger: u32,
+}
+
+// kKALE, the SEP-41 receipt token for staked KALE. Each balance is the shares held by a
+// staking position, so moving kKALE moves the stake along with its future rewards; rewards
+// already settled stay with the sender.
+#[contractimpl]
+impl TokenInterface for KaleLendingPlatform {
+    fn allowance(env: Env, from: Address, spender: Address) -> i128 {
+        read_allowance(&env, &from, &spender)
+    }
+
+    fn approve(env: Env, from: Address, spender: Address, amount: i128, expiration_ledger: u32) {
+        from.require_auth();
+
+        if amount < 0 || (amount > 0 && expiration_ledger < env.ledger().sequence()) {
+            panic_with_error!(&env, Error::InvalidAmount);
+        }
+
+        DataKey::Allowance(from.clone(), spender.clone()).set(
+            &env,
+            &AllowanceValue {
+                amount,
+                expiration_ledger,
+            },
+        );
+
+        events::publish_approve(&env, &from, &spender, amount, expiration_ledger);
+    }
+
+    fn balance(env: Env, id: Address) -> i128 {
+        DataKey::Stake(id)
+            .get::<StakingPosition>(&env)
+            .map(|position| position.shares)
+            .unwrap_or(0)
+    }
+
+    fn transfer(env: Env, from: Address, to: Address, amount: i128) {
+        from.require_auth();
+
+        move_shares(&env, &from, &to, amount);
+        events::publish_transfer(&env, &from, &to, amount);
+    }
+
+    fn transfer_from(env: Env, spender: Address, from: Address, to: Address, amount: i128) {
+        spender.require_auth();
+
+        spend_allowance(&env, &from, &spender, amount);
+        move_shares(&env, &from, &to, amount);
+        events::publish_transfer(&env, &from, &to, amount);
+    }
+
+    fn burn(env: Env, from: Address, amount: i128) {
+        from.require_auth();
+
+        burn_shares(&env, &from, amount);
+    }
+
+    fn burn_from(env: Env, spender: Address, from: Address, amount: i128) {
+        spender.require_auth();
+
+        spend_allowance(&env, &from, &spender, amount);
+        burn_shares(&env, &from, amount);
+    }
+
+    fn decimals(env: Env) -> u32 {
+        let state = load_state(&env);
+        token::Client::new(&env, &state.kale_token).decimals()
+    }
+
+    fn name(env: Env) -> String {
+        String::from_str(&env, "Staked KALE")
+    }
+
+    fn symbol(env: Env) -> String {
+        String::from_str(&env, "kKALE")
+    }
+}
+
+// Unexpired allowance from `from` to `spender`
+fn read_allowance(env: &Env, from: &Address, spender: &Address) -> i128 {
+    match DataKey::Allowance(from.clone(), spender.clone()).get::<AllowanceValue>(env) {
+        Some(allowance) if allowance.expiration_ledger >= env.ledger().sequence() => allowance.amount,
+        _ => 0,
+    }
+}
+
+fn spend_allowance(env: &Env, from: &Address, spender: &Address, amount: i128) {
+    let allowance = read_allowance(env, from, spender);
+    if amount > allowance {
+        panic_with_error!(env, Error::InsufficientAllowance);
+    }
+    if amount > 0 {
+        let key = DataKey::Allowance(from.clone(), spender.clone());
+        let mut value: AllowanceValue = key.get(env).unwrap();
+        value.amount -= amount;
+        key.set(env, &value);
+    }
+}
+
+// Move shares between positions, opening one for the recipient if needed. Both sides are
+// settled first so rewards up to now go to whoever held the shares while they accrued.
+fn move_shares(env: &Env, from: &Address, to: &Address, amount: i128) {
+    if amount < 0 {
+        panic_with_error!(env, Error::InvalidAmount);
+    }
+
+    let state = load_state(env);
+    let mut sender = load_position(env, &state, from, amount);
+    if from == to {
+        return;
+    }
+
+    let mut recipient = match DataKey::Stake(to.clone()).get::<StakingPosition>(env) {
+        Some(mut position) => {
+            unwrap(env, KaleLendingPlatform::settle_staking_rewards(env, &state, &mut position));
+            position
+        }
+        None => KaleLendingPlatform::new_staking_position(env, &state, to, state.current_kale_price),
+    };
+    unwrap(env, KaleLendingPlatform::settle_staking_rewards(env, &state, &mut sender));
+
+    sender.shares -= amount;
+    recipient.shares += amount;
+    sender.kale_amount = KaleLendingPlatform::shares_to_kale(&state, sender.shares);
+    recipient.kale_amount = KaleLendingPlatform::shares_to_kale(&state, recipient.shares);
+
+    save_position(env, from, &sender);
+    save_position(env, to, &recipient);
+    StorageKey::PlatformState.set(env, &state);
+}
+
+// Burning kKALE redeems it: the KALE the shares are worth is paid out to their owner,
+// exactly as if the position had been unstaked
+fn burn_shares(env: &Env, from: &Address, amount: i128) {
+    if amount < 0 {
+        panic_with_error!(env, Error::InvalidAmount);
+    }
+
+    let mut state = load_state(env);
+    let mut position = load_position(env, &state, from, amount);
+    unwrap(env, KaleLendingPlatform::settle_staking_rewards(env, &state, &mut position));
+
+    let kale_amount = if amount == position.shares {
+        position.kale_amount
+    } else {
+        KaleLendingPlatform::shares_to_kale(&state, amount)
+    };
+    // KALE currently lent to borrowers can only be redeemed once repaid
+    if kale_amount > KaleLendingPlatform::available_liquidity(env, &state) {
+        panic_with_error!(env, Error::InsufficientLiquidity);
+    }
+    KaleLendingPlatform::redeem_shares(env, &mut state, from, &mut position, amount, kale_amount);
+
+    save_position(env, from, &position);
+    StorageKey::PlatformState.set(env, &state);
+}
+
+// Platform state with borrow interest and staking rewards brought up to now
+fn load_state(env: &Env) -> PlatformState {
+    let mut state: PlatformState = match StorageKey::PlatformState.get(env) {
+        Some(state) => state,
+        None => panic_with_error!(env, Error::NotInitialized),
+    };
+    let config: PlatformConfig = match StorageKey::PlatformConfig.get(env) {
+        Some(config) => config,
+        None => panic_with_error!(env, Error::NotInitialized),
+    };
+    KaleLendingPlatform::accrue_borrow_index(env, &config, &mut state);
+    KaleLendingPlatform::update_reward_per_token(env, &mut state);
+    state
+}
+
+// Position of a holder that must own at least `amount` shares
+fn load_position(env: &Env, state: &PlatformState, user: &Address, amount: i128) -> StakingPosition {
+    let position = DataKey::Stake(user.clone())
+        .get::<StakingPosition>(env)
+        .unwrap_or_else(|| KaleLendingPlatform::new_staking_position(env, state, user, state.current_kale_price));
+    if position.shares < amount {
+        panic_with_error!(env, Error::InsufficientStake);
+    }
+    position
+}
+
+// Store a position, dropping it once it holds no shares and no unclaimed rewards
+fn save_position(env: &Env, user: &Address, position: &StakingPosition) {
+    if position.shares == 0 && position.pending_rewards == 0 {
+        DataKey::Stake(user.clone()).remove(env);
+    } else {
+        DataKey::Stake(user.clone()).set(env, position);
+    }
+}
+
+fn unwrap<T>(env: &Env, result: Result<T, Error>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(error) => panic_with_error!(env, error),
+    }
+}
@@ -0,0 +1,191 @@
+#![cfg(any(test, feature = "testutils"))]
+
+use crate::reflector::{Asset, Contract, PriceData};
+use soroban_sdk::{contract, contractimpl, contracttype, symbol_short, Address, Env, Vec};
+
+// Mock Reflector oracle with settable price history, decimals and resolution, so the
+// pricing path can be exercised without a live feed.
+#[contract]
+pub struct MockReflector;
+
+#[contracttype]
+#[derive(Clone)]
+enum MockKey {
+    Assets,
+    Prices(Asset),
+    CrossPrice(Asset, Asset),
+    Decimals,
+    Resolution,
+    LastTimestamp,
+}
+
+const DEFAULT_DECIMALS: u32 = 14;
+const DEFAULT_RESOLUTION: u32 = 300; // 5 minutes
+const RETENTION_PERIOD: u64 = 24 * 60 * 60;
+
+#[contractimpl]
+impl MockReflector {
+    // Record a price for an asset at the given timestamp
+    pub fn set_price(env: Env, asset: Asset, price: i128, timestamp: u64) {
+        let storage = env.storage().instance();
+
+        let mut assets: Vec<Asset> = storage.get(&MockKey::Assets).unwrap_or(Vec::new(&env));
+        if !assets.contains(&asset) {
+            assets.push_back(asset.clone());
+            storage.set(&MockKey::Assets, &assets);
+        }
+
+        let key = MockKey::Prices(asset);
+        let mut history: Vec<PriceData> = storage.get(&key).unwrap_or(Vec::new(&env));
+        history.push_back(PriceData { price, timestamp });
+        storage.set(&key, &history);
+
+        let last_timestamp: u64 = storage.get(&MockKey::LastTimestamp).unwrap_or(0);
+        if timestamp > last_timestamp {
+            storage.set(&MockKey::LastTimestamp, &timestamp);
+        }
+    }
+
+    // Pin the quote `x_last_price` returns for a pair, e.g. to simulate a cross feed that
+    // disagrees with the USD quotes
+    pub fn set_cross_price(env: Env, base_asset: Asset, quote_asset: Asset, price: i128, timestamp: u64) {
+        let key = MockKey::CrossPrice(base_asset, quote_asset);
+        env.storage().instance().set(&key, &PriceData { price, timestamp });
+    }
+
+    pub fn set_decimals(env: Env, decimals: u32) {
+        env.storage().instance().set(&MockKey::Decimals, &decimals);
+    }
+
+    pub fn set_resolution(env: Env, resolution: u32) {
+        env.storage().instance().set(&MockKey::Resolution, &resolution);
+    }
+}
+
+#[contractimpl]
+impl Contract for MockReflector {
+    fn base(_e: Env) -> Asset {
+        Asset::Other(symbol_short!("USD"))
+    }
+
+    fn assets(e: Env) -> Vec<Asset> {
+        e.storage().instance().get(&MockKey::Assets).unwrap_or(Vec::new(&e))
+    }
+
+    fn decimals(e: Env) -> u32 {
+        e.storage().instance().get(&MockKey::Decimals).unwrap_or(DEFAULT_DECIMALS)
+    }
+
+    fn price(e: Env, asset: Asset, timestamp: u64) -> Option<PriceData> {
+        history(&e, asset)
+            .iter()
+            .rev()
+            .find(|record| record.timestamp <= timestamp)
+    }
+
+    fn lastprice(e: Env, asset: Asset) -> Option<PriceData> {
+        history(&e, asset).last()
+    }
+
+    fn prices(e: Env, asset: Asset, records: u32) -> Option<Vec<PriceData>> {
+        latest(&e, &history(&e, asset), records)
+    }
+
+    fn x_last_price(e: Env, base_asset: Asset, quote_asset: Asset) -> Option<PriceData> {
+        let key = MockKey::CrossPrice(base_asset.clone(), quote_asset.clone());
+        if let Some(pinned) = e.storage().instance().get(&key) {
+            return Some(pinned);
+        }
+
+        let base = Self::lastprice(e.clone(), base_asset)?;
+        let quote = Self::lastprice(e.clone(), quote_asset)?;
+        cross(&e, &base, &quote)
+    }
+
+    fn x_price(e: Env, base_asset: Asset, quote_asset: Asset, timestamp: u64) -> Option<PriceData> {
+        let base = Self::price(e.clone(), base_asset, timestamp)?;
+        let quote = Self::price(e.clone(), quote_asset, timestamp)?;
+        cross(&e, &base, &quote)
+    }
+
+    fn x_prices(e: Env, base_asset: Asset, quote_asset: Asset, records: u32) -> Option<Vec<PriceData>> {
+        let base = Self::prices(e.clone(), base_asset, records)?;
+        let quote = Self::prices(e.clone(), quote_asset, records)?;
+        if base.len() != quote.len() {
+            return None;
+        }
+
+        let mut result = Vec::new(&e);
+        for (base, quote) in base.iter().zip(quote.iter()) {
+            result.push_back(cross(&e, &base, &quote)?);
+        }
+        Some(result)
+    }
+
+    fn twap(e: Env, asset: Asset, records: u32) -> Option<i128> {
+        average(&Self::prices(e, asset, records)?)
+    }
+
+    fn x_twap(e: Env, base_asset: Asset, quote_asset: Asset, records: u32) -> Option<i128> {
+        average(&Self::x_prices(e, base_asset, quote_asset, records)?)
+    }
+
+    fn resolution(e: Env) -> u32 {
+        e.storage().instance().get(&MockKey::Resolution).unwrap_or(DEFAULT_RESOLUTION)
+    }
+
+    fn period(_e: Env) -> Option<u64> {
+        Some(RETENTION_PERIOD)
+    }
+
+    fn last_timestamp(e: Env) -> u64 {
+        e.storage().instance().get(&MockKey::LastTimestamp).unwrap_or(0)
+    }
+
+    fn version(_e: Env) -> u32 {
+        1
+    }
+
+    fn admin(_e: Env) -> Option<Address> {
+        None
+    }
+}
+
+fn history(e: &Env, asset: Asset) -> Vec<PriceData> {
+    e.storage().instance().get(&MockKey::Prices(asset)).unwrap_or(Vec::new(e))
+}
+
+// The most recent `records` entries, newest first, like Reflector returns them
+fn latest(e: &Env, history: &Vec<PriceData>, records: u32) -> Option<Vec<PriceData>> {
+    if history.is_empty() || records == 0 {
+        return None;
+    }
+
+    let mut result = Vec::new(e);
+    for record in history.iter().rev().take(records as usize) {
+        result.push_back(record);
+    }
+    Some(result)
+}
+
+// Price of `base` quoted in `quote`, keeping the oracle's decimals
+fn cross(e: &Env, base: &PriceData, quote: &PriceData) -> Option<PriceData> {
+    if quote.price == 0 {
+        return None;
+    }
+
+    let decimals = MockReflector::decimals(e.clone());
+    Some(PriceData {
+        price: base.price * 10i128.pow(decimals) / quote.price,
+        timestamp: base.timestamp.min(quote.timestamp),
+    })
+}
+
+fn average(records: &Vec<PriceData>) -> Option<i128> {
+    if records.is_empty() {
+        return None;
+    }
+
+    let sum: i128 = records.iter().map(|record| record.price).sum();
+    Some(sum / records.len() as i128)
+}
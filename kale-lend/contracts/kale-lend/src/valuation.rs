@@ -0,0 +1,115 @@
+use soroban_sdk::{token, Env};
+
+use crate::{Error, PlatformState};
+
+// Decimals of every USD value produced here, independent of the oracle's own precision
+pub const USD_DECIMALS: u32 = 14;
+
+// Prices a KALE/XLM position is valued at: separate USD quotes for each token, or
+// KALE quoted directly in XLM. Both are at USD_DECIMALS, whichever oracle quoted them.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Quote {
+    Usd { kale_price: i128, xlm_price: i128 },
+    Cross(i128),
+}
+
+// Converts token amounts to and from fixed-point USD values, using the oracle's price
+// decimals and each token's own decimals instead of assuming a shared precision.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Valuation {
+    pub oracle_decimals: u32,
+    pub kale_decimals: u32,
+    pub xlm_decimals: u32,
+}
+
+impl Valuation {
+    pub fn new(oracle_decimals: u32, kale_decimals: u32, xlm_decimals: u32) -> Self {
+        Valuation {
+            oracle_decimals,
+            kale_decimals,
+            xlm_decimals,
+        }
+    }
+
+    // Read the current decimals of both token contracts. Oracle prices are already
+    // rescaled to USD_DECIMALS when they are read, whichever oracle answered.
+    pub fn load(env: &Env, state: &PlatformState) -> Self {
+        Valuation::new(
+            USD_DECIMALS,
+            token::Client::new(env, &state.kale_token).decimals(),
+            token::Client::new(env, &state.xlm_token).decimals(),
+        )
+    }
+
+    pub fn kale_value(&self, amount: i128, price: i128) -> Result<i128, Error> {
+        self.value(amount, price, self.kale_decimals)
+    }
+
+    pub fn xlm_value(&self, amount: i128, price: i128) -> Result<i128, Error> {
+        self.value(amount, price, self.xlm_decimals)
+    }
+
+    // Amount of XLM worth `value` USD at `price`, rounded down
+    pub fn xlm_for_value(&self, value: i128, price: i128) -> Result<i128, Error> {
+        if price <= 0 {
+            return Err(Error::OracleUnavailable);
+        }
+
+        let exponent = (self.xlm_decimals + self.oracle_decimals) as i32 - USD_DECIMALS as i32;
+        let scaled = rescale(value, exponent)?;
+        Ok(scaled / price)
+    }
+
+    // Amount of XLM worth `kale_amount` KALE, rounded down
+    pub fn kale_in_xlm(&self, kale_amount: i128, quote: &Quote) -> Result<i128, Error> {
+        match *quote {
+            Quote::Usd { kale_price, xlm_price } => {
+                self.xlm_for_value(self.kale_value(kale_amount, kale_price)?, xlm_price)
+            }
+            Quote::Cross(price) => {
+                let raw = kale_amount.checked_mul(price).ok_or(Error::MathOverflow)?;
+                let exponent = self.xlm_decimals as i32
+                    - (self.kale_decimals + self.oracle_decimals) as i32;
+                rescale(raw, exponent)
+            }
+        }
+    }
+
+    // XLM collateral / KALE debt ratio in basis points
+    pub fn collateral_ratio(&self, xlm_amount: i128, kale_amount: i128, quote: &Quote) -> Result<i128, Error> {
+        let (collateral_value, debt_value) = match *quote {
+            Quote::Usd { kale_price, xlm_price } => (
+                self.xlm_value(xlm_amount, xlm_price)?,
+                self.kale_value(kale_amount, kale_price)?,
+            ),
+            Quote::Cross(_) => (xlm_amount, self.kale_in_xlm(kale_amount, quote)?),
+        };
+        if debt_value <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+
+        collateral_value
+            .checked_mul(10000)
+            .map(|scaled| scaled / debt_value)
+            .ok_or(Error::MathOverflow)
+    }
+
+    // amount * price, moved from token + oracle decimals to USD_DECIMALS
+    fn value(&self, amount: i128, price: i128, token_decimals: u32) -> Result<i128, Error> {
+        let raw = amount.checked_mul(price).ok_or(Error::MathOverflow)?;
+        let exponent = USD_DECIMALS as i32 - (token_decimals + self.oracle_decimals) as i32;
+        rescale(raw, exponent)
+    }
+}
+
+// Multiply by 10^exponent, or divide (rounding down) when the exponent is negative
+pub fn rescale(value: i128, exponent: i32) -> Result<i128, Error> {
+    let factor = 10i128
+        .checked_pow(exponent.unsigned_abs())
+        .ok_or(Error::MathOverflow)?;
+    if exponent >= 0 {
+        value.checked_mul(factor).ok_or(Error::MathOverflow)
+    } else {
+        Ok(value / factor)
+    }
+}